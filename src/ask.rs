@@ -0,0 +1,105 @@
+use eyre::Result;
+
+use crate::Transcript;
+use crate::cite::timestamped_text;
+use crate::llm::{Chat, Llm, Message, Reply, TokenUsage};
+
+const ASK_SYSTEM_PROMPT: &str = "You answer questions about a YouTube video from its transcript, which follows. \
+Each line of the transcript starts with the time it is spoken, as [mm:ss] or [h:mm:ss]. \
+Answer from the transcript alone and say so when it doesn't cover the question. \
+Keep answers short, and end each claim with the times of the lines behind it, copied exactly, e.g. [12:34].";
+
+/// Questions and answers about one transcript. The transcript is sent as
+/// the same context every turn, so providers with prompt caching bill
+/// follow-ups a fraction of the first question.
+#[derive(Debug, Clone)]
+pub struct Conversation {
+    context: String,
+    messages: Vec<Message>,
+}
+
+impl Conversation {
+    pub fn new(transcript: &Transcript) -> Self {
+        Self {
+            context: format!(
+                "Transcript of \"{}\":\n{}",
+                transcript.title,
+                timestamped_text(&transcript.segments)
+            ),
+            messages: Vec::new(),
+        }
+    }
+
+    /// The questions and answers so far
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// Ask the next question, with the earlier ones and their answers. The
+    /// answer is streamed to stdout; a failed question is left out of the
+    /// history.
+    pub async fn ask(&mut self, client: &reqwest::Client, llm: &Llm, question: &str) -> Result<(String, TokenUsage)> {
+        self.messages.push(Message::user(question));
+        let chat = Chat {
+            system: ASK_SYSTEM_PROMPT,
+            context: Some(&self.context),
+            messages: &self.messages,
+        };
+        let reply = llm.chat(client, &chat, Reply::Stream).await;
+        self.answered(reply)
+    }
+
+    /// Record the reply to the question just asked, or drop the question
+    /// if there was none
+    fn answered(&mut self, reply: Result<(String, TokenUsage)>) -> Result<(String, TokenUsage)> {
+        match reply {
+            Ok((answer, usage)) => {
+                self.messages.push(Message::assistant(answer.clone()));
+                Ok((answer, usage))
+            }
+            Err(e) => {
+                self.messages.pop();
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Segment;
+
+    fn transcript() -> Transcript {
+        let segments = vec![Segment {
+            text: "It costs ten dollars.".to_string(),
+            start: 62.0,
+            duration: 3.0,
+            ..Default::default()
+        }];
+        Transcript::for_test("abc123", "Pricing talk", segments)
+    }
+
+    #[test]
+    fn test_context_is_timestamped() {
+        let conversation = Conversation::new(&transcript());
+        assert_eq!(
+            conversation.context,
+            "Transcript of \"Pricing talk\":\n[01:02] It costs ten dollars."
+        );
+        assert!(conversation.messages().is_empty());
+    }
+
+    #[test]
+    fn test_failed_question_leaves_no_history() {
+        let mut conversation = Conversation::new(&transcript());
+        conversation.messages.push(Message::user("Price?"));
+        let answer = conversation.answered(Ok(("Ten dollars [01:02].".to_string(), TokenUsage::default())));
+        assert_eq!(answer.unwrap().0, "Ten dollars [01:02].");
+        assert_eq!(conversation.messages().len(), 2);
+
+        conversation.messages.push(Message::user("And in euros?"));
+        assert!(conversation.answered(Err(eyre::eyre!("connection refused"))).is_err());
+        assert_eq!(conversation.messages().len(), 2);
+    }
+}
@@ -3,28 +3,55 @@ use std::path::PathBuf;
 use eyre::Result;
 use log::debug;
 
-use crate::Transcript;
+use crate::summarize::Summary;
+use crate::{AUTO_LANG, Transcript};
 
 fn cache_dir() -> PathBuf {
-    dirs::cache_dir()
-        .unwrap_or_else(|| PathBuf::from(".cache"))
-        .join("ytx")
-        .join("transcripts")
+    ytx_cache_dir().join("transcripts")
+}
+
+fn summaries_dir() -> PathBuf {
+    ytx_cache_dir().join("summaries")
+}
+
+fn ytx_cache_dir() -> PathBuf {
+    dirs::cache_dir().unwrap_or_else(|| PathBuf::from(".cache")).join("ytx")
 }
 
 fn cache_path(video_id: &str, lang: &str) -> PathBuf {
     cache_dir().join(format!("{video_id}-{lang}.json"))
 }
 
-/// Load a cached transcript, if available.
+/// Load a cached transcript, if available. With [`AUTO_LANG`], a transcript
+/// in any language matches.
 pub fn load(video_id: &str, lang: &str) -> Option<Transcript> {
-    let path = cache_path(video_id, lang);
+    let path = if lang == AUTO_LANG {
+        find_any_language(video_id)?
+    } else {
+        cache_path(video_id, lang)
+    };
     let data = std::fs::read_to_string(&path).ok()?;
     let transcript: Transcript = serde_json::from_str(&data).ok()?;
     debug!("Cache hit: {}", path.display());
     Some(transcript)
 }
 
+fn find_any_language(video_id: &str) -> Option<PathBuf> {
+    // Video IDs are a fixed 11 characters, so this prefix can't match another video
+    let prefix = format!("{video_id}-");
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(cache_dir())
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(&prefix) && n.ends_with(".json"))
+        })
+        .collect();
+    paths.sort();
+    paths.into_iter().next()
+}
+
 /// Save a transcript to the cache.
 pub fn save(transcript: &Transcript) -> Result<()> {
     let path = cache_path(&transcript.video_id, &transcript.language);
@@ -34,3 +61,25 @@ pub fn save(transcript: &Transcript) -> Result<()> {
     debug!("Cached transcript: {}", path.display());
     Ok(())
 }
+
+fn summary_path(video_id: &str, key: &str) -> PathBuf {
+    summaries_dir().join(format!("{video_id}-{key}.json"))
+}
+
+/// Load a cached summary by its key from [`crate::summarize::cache_key`]
+pub fn load_summary(video_id: &str, key: &str) -> Option<Summary> {
+    let path = summary_path(video_id, key);
+    let data = std::fs::read_to_string(&path).ok()?;
+    let summary: Summary = serde_json::from_str(&data).ok()?;
+    debug!("Summary cache hit: {}", path.display());
+    Some(summary)
+}
+
+/// Save a summary to the cache under its key
+pub fn save_summary(video_id: &str, key: &str, summary: &Summary) -> Result<()> {
+    let path = summary_path(video_id, key);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(&path, serde_json::to_string_pretty(summary)?)?;
+    debug!("Cached summary: {}", path.display());
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use std::collections::HashSet;
+
+use regex::{Captures, Regex};
+
+use crate::{Segment, Transcript};
+
+/// Asks for each point of a summary to cite the transcript lines behind it
+pub const CITE_INSTRUCTIONS: &str = "Each line of the transcript starts with the time it is spoken, \
+as [mm:ss] or [h:mm:ss]. End every bullet point or claim with the times of the lines that support it, \
+copied exactly in the same form, e.g. [12:34] or [12:34, 15:02]. Only cite times that appear in the transcript.";
+
+/// Asks for citations already in part summaries to be carried through
+pub const KEEP_CITATIONS: &str = "The summaries you are given cite transcript times as [mm:ss] or [h:mm:ss]. \
+Keep those citations, copied exactly, at the end of each point they support.";
+
+/// A summary with its citations checked against the transcript
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cited {
+    pub text: String,
+    /// Cited times that no transcript line starts at, as written
+    pub dropped: Vec<String>,
+}
+
+/// `[mm:ss]`, or `[h:mm:ss]` from an hour in
+pub fn stamp(seconds: f64) -> String {
+    let secs = seconds as u64;
+    if secs >= 3600 {
+        format!("[{}:{:02}:{:02}]", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("[{:02}:{:02}]", secs / 60, secs % 60)
+    }
+}
+
+/// One `[mm:ss] text` line per segment
+pub fn timestamped_text(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .map(|s| format!("{} {}", stamp(s.start), s.text))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Watch link starting at `seconds`
+pub fn video_url(video_id: &str, seconds: u64) -> String {
+    format!("https://youtu.be/{video_id}?t={seconds}")
+}
+
+/// Drop every cited time that isn't the start of a transcript line, and
+/// the brackets left empty by that. Lines are stamped with their whole
+/// starting second, so a genuine citation matches one exactly.
+pub fn check(text: &str, transcript: &Transcript) -> Cited {
+    let starts: HashSet<u64> = transcript.segments.iter().map(|s| s.start as u64).collect();
+    let mut dropped = Vec::new();
+
+    let text = citation()
+        .replace_all(text, |caps: &Captures| {
+            let (valid, invalid): (Vec<_>, Vec<_>) = times(&caps[2])
+                .into_iter()
+                .partition(|(_, secs)| secs.is_some_and(|s| starts.contains(&s)));
+            dropped.extend(invalid.into_iter().map(|(label, _)| label.to_string()));
+            if valid.is_empty() {
+                return String::new();
+            }
+            let labels: Vec<&str> = valid.iter().map(|(label, _)| *label).collect();
+            format!("{}[{}]", &caps[1], labels.join(", "))
+        })
+        .into_owned();
+
+    Cited { text, dropped }
+}
+
+/// Replace each citation with `render` of its times, as (label, seconds)
+pub fn replace(text: &str, render: impl Fn(&[(&str, u64)]) -> String) -> String {
+    citation()
+        .replace_all(text, |caps: &Captures| {
+            let found: Vec<(&str, u64)> = times(&caps[2])
+                .into_iter()
+                .filter_map(|(label, secs)| Some((label, secs?)))
+                .collect();
+            format!("{}{}", &caps[1], render(&found))
+        })
+        .into_owned()
+}
+
+/// A bracketed list of one or more times, with the spaces before it on
+/// its line. Ranges aren't asked for and aren't citations: only their start
+/// could be checked against the transcript.
+fn citation() -> Regex {
+    Regex::new(r"([ \t]*)\[(\d{1,2}(?::\d{2}){1,2}(?:\s*[,;]\s*\d{1,2}(?::\d{2}){1,2})*)\]").expect("valid regex")
+}
+
+/// Each time in a citation, with its value in seconds if it reads as one
+fn times(list: &str) -> Vec<(&str, Option<u64>)> {
+    list.split([',', ';'])
+        .map(str::trim)
+        .map(|label| (label, seconds(label)))
+        .collect()
+}
+
+/// `mm:ss` or `h:mm:ss` in seconds
+pub fn seconds(label: &str) -> Option<u64> {
+    let fields: Vec<u64> = label.split(':').map(|f| f.parse().ok()).collect::<Option<_>>()?;
+    let (&secs, rest) = fields.split_last()?;
+    if secs >= 60 || rest.iter().skip(1).any(|&mins| mins >= 60) {
+        return None;
+    }
+    Some(rest.iter().fold(0, |total, field| total * 60 + field) * 60 + secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TranscriptSource;
+
+    fn transcript(starts: &[f64]) -> Transcript {
+        Transcript {
+            video_id: "abc123".to_string(),
+            title: "Talk".to_string(),
+            language: "en".to_string(),
+            source_language: None,
+            source: TranscriptSource::Caption,
+            segments: starts
+                .iter()
+                .map(|&start| Segment {
+                    text: "words".to_string(),
+                    start,
+                    duration: 4.0,
+                    ..Default::default()
+                })
+                .collect(),
+            speakers: Default::default(),
+            quality: None,
+        }
+    }
+
+    #[test]
+    fn test_stamp() {
+        assert_eq!(stamp(0.0), "[00:00]");
+        assert_eq!(stamp(754.9), "[12:34]");
+        assert_eq!(stamp(3723.0), "[1:02:03]");
+    }
+
+    #[test]
+    fn test_seconds() {
+        assert_eq!(seconds("12:34"), Some(754));
+        assert_eq!(seconds("1:02:03"), Some(3723));
+        assert_eq!(seconds("12:75"), None);
+        assert_eq!(seconds("1:75:00"), None);
+    }
+
+    #[test]
+    fn test_check_drops_unknown_times() {
+        let transcript = transcript(&[0.0, 62.4, 754.2, 3723.0]);
+        let cited = check(
+            "- Intro [00:00]\n- Setup [01:02, 01:03]\n- Made up [09:59]\n- Late [1:02:03]",
+            &transcript,
+        );
+        assert_eq!(
+            cited.text,
+            "- Intro [00:00]\n- Setup [01:02]\n- Made up\n- Late [1:02:03]"
+        );
+        assert_eq!(cited.dropped, vec!["01:03", "09:59"]);
+
+        let ranges = "- Setup [01:02-01:30]\n- Demo [12:34 – 13:00]";
+        let cited = check(ranges, &transcript);
+        assert_eq!(cited.text, ranges);
+        assert!(cited.dropped.is_empty());
+    }
+
+    #[test]
+    fn test_replace() {
+        let linked = replace("Point [12:34, 15:02]. Not a time [1]", |times| {
+            times
+                .iter()
+                .map(|(label, secs)| format!("<{label}={secs}>"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        });
+        assert_eq!(linked, "Point <12:34=754> <15:02=902>. Not a time [1]");
+        assert_eq!(
+            replace("Range [01:02-01:30]", |_| "<link>".to_string()),
+            "Range [01:02-01:30]"
+        );
+    }
+}
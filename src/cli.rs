@@ -1,4 +1,5 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
@@ -6,6 +7,31 @@ pub enum OutputFormat {
     Text,
     Json,
     Srt,
+    Vtt,
+    Markdown,
+    Html,
+}
+
+impl OutputFormat {
+    /// Whether a summary goes inside the output, rather than apart from it
+    pub fn holds_summary(self) -> bool {
+        matches!(self, OutputFormat::Json | OutputFormat::Markdown | OutputFormat::Html)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SummaryFormat {
+    Text,
+    Markdown,
+    Html,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum UsageFormat {
+    Table,
+    Json,
+    Csv,
 }
 
 #[derive(Parser)]
@@ -15,6 +41,9 @@ pub enum OutputFormat {
     version = env!("GIT_DESCRIBE"),
 )]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// YouTube video URL or video ID (reads from stdin if omitted)
     pub url: Option<String>,
 
@@ -22,31 +51,164 @@ pub struct Cli {
     #[arg(short, long)]
     pub summarize: bool,
 
-    /// Output format: text (default), json, srt
+    /// Print the summary as it is generated instead of all at once
+    #[arg(long)]
+    pub stream: bool,
+
+    /// Summarize with a prompt template: built in (summary, tldr, notes, wisdom,
+    /// action-items) or NAME.md in ~/.config/ytx/prompts/; implies --summarize.
+    /// In a template file, text above the first line that is just `---` is the
+    /// system prompt; later `---` lines are kept as Markdown rules, so start
+    /// the file with `---` to use rules without a system prompt
+    #[arg(long, value_name = "NAME", conflicts_with = "prompt_file")]
+    pub prompt: Option<String>,
+
+    /// Summarize with the prompt template in this file, laid out as for
+    /// --prompt; implies --summarize
+    #[arg(long, value_name = "PATH")]
+    pub prompt_file: Option<PathBuf>,
+
+    /// Cite the [mm:ss] times behind each point of the summary, checked
+    /// against the transcript; implies --summarize and can't be streamed
+    #[arg(long)]
+    pub cite: bool,
+
+    /// Summary format: text (default), markdown, html, or json with a fixed
+    /// schema (tl;dr, key points, quotes, people, tools, action items);
+    /// markdown and html link citations to those moments in the video
+    #[arg(long, value_enum, default_value_t = SummaryFormat::Text)]
+    pub summary_format: SummaryFormat,
+
+    /// Output format: text (default), json, srt, vtt, markdown, html; json,
+    /// markdown and html include the summary, if there is one
     #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
     pub format: OutputFormat,
 
-    /// Preferred caption language
-    #[arg(short, long, default_value = "en")]
+    /// Preferred caption language, or "auto" to use the video's own language
+    /// and have Whisper detect it
+    #[arg(short, long, default_value = "en", global = true)]
     pub lang: String,
 
     /// Write output to file instead of stdout
     #[arg(short, long)]
     pub output: Option<PathBuf>,
 
-    /// Skip caption extraction, always use Whisper
+    /// Write the summary, in --summary-format, to this file; with text, srt
+    /// and vtt output it's otherwise printed after the transcript
+    #[arg(long, value_name = "PATH")]
+    pub summary_output: Option<PathBuf>,
+
+    /// Ignore the configured fetch tiers and always use Whisper
     #[arg(long)]
     pub whisper_only: bool,
 
-    /// Bypass cache and re-fetch from YouTube
+    /// Translate the speech into English with Whisper (whisper-1 only);
+    /// English captions are still used when the video has them
+    #[arg(long)]
+    pub translate: bool,
+
+    /// Transcription model: whisper-1, gpt-4o-transcribe, gpt-4o-mini-transcribe,
+    /// or gpt-4o-transcribe-diarize for speaker labels
+    #[arg(long)]
+    pub whisper_model: Option<ytx::whisper::WhisperModel>,
+
+    /// Names for diarized speaker labels, e.g. "A=Alice,B=Bob"
+    #[arg(long, value_parser = ytx::parse_speaker_names)]
+    pub speakers: Option<BTreeMap<String, String>>,
+
+    /// Maximum number of audio chunks uploaded to Whisper at once. With 1
+    /// (the default) each chunk is prompted with the closing text of the one
+    /// before; more uploads faster but gives that up, so names and words
+    /// across chunk boundaries may be transcribed less consistently
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    pub whisper_jobs: u16,
+
+    /// Cut long silences out of the audio before paid transcription
+    /// (needs ffmpeg); timestamps still refer to the original video
     #[arg(long)]
+    pub trim_silence: bool,
+
+    /// Speed the audio up by this factor (1.0-2.0) before paid transcription
+    /// to pay for fewer minutes (needs ffmpeg)
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.0, value_parser = ytx::whisper::parse_speed)]
+    pub speed: f64,
+
+    /// Refuse (or ask, when interactive) before paid transcription would push
+    /// this run's estimated spend past this many US dollars
+    #[arg(long, value_name = "USD")]
+    pub max_cost: Option<f64>,
+
+    /// Project to attribute paid API calls to in the usage ledger
+    #[arg(long)]
+    pub project: Option<String>,
+
+    /// Keep the downloaded audio in the cache directory instead of deleting it
+    #[arg(long)]
+    pub keep_audio: bool,
+
+    /// Directory for temporary files (default: system temp dir)
+    #[arg(long)]
+    pub temp_dir: Option<PathBuf>,
+
+    /// Bypass the transcript and summary caches: re-fetch from YouTube and re-summarize
+    #[arg(long, global = true)]
     pub no_cache: bool,
 
-    /// LLM model for summarization
-    #[arg(long, default_value = "claude-sonnet-4-6")]
+    /// LLM model for summaries and `ask`, optionally as provider:model
+    /// (e.g. ollama:llama3.1, gemini:gemini-2.5-flash, azure:DEPLOYMENT)
+    #[arg(long, default_value = "claude-sonnet-4-6", global = true)]
     pub model: String,
 
     /// Show extraction method and metadata
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub verbose: bool,
 }
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Report recorded API spend from the usage ledger
+    Usage(UsageArgs),
+    /// List the models an LLM provider offers
+    Models(ModelsArgs),
+    /// Ask questions about a video, answered from its transcript
+    Ask(AskArgs),
+}
+
+#[derive(Args)]
+pub struct AskArgs {
+    /// YouTube video URL or video ID
+    pub url: String,
+
+    /// Answer this question and exit, instead of asking on stdin in turn
+    pub question: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ModelsArgs {
+    /// anthropic, openai, gemini, mistral or ollama (default: llm_provider
+    /// from config, else anthropic)
+    pub provider: Option<String>,
+}
+
+#[derive(Args)]
+pub struct UsageArgs {
+    /// Group totals by day, model, video, user or project
+    #[arg(long, value_enum, default_value_t = ytx::usage::GroupBy::Day)]
+    pub by: ytx::usage::GroupBy,
+
+    /// Report format: table (default), json, csv
+    #[arg(short, long, value_enum, default_value_t = UsageFormat::Table)]
+    pub format: UsageFormat,
+
+    /// Only include calls on or after this day (YYYY-MM-DD, UTC)
+    #[arg(long, value_parser = ytx::usage::parse_day)]
+    pub since: Option<String>,
+
+    /// Only include calls made by this user
+    #[arg(long)]
+    pub user: Option<String>,
+
+    /// Only include calls attributed to this project
+    #[arg(long)]
+    pub project: Option<String>,
+}
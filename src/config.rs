@@ -1,9 +1,12 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use eyre::Result;
 use log::debug;
 use serde::{Deserialize, Serialize};
 
+use crate::policy::Tier;
+
 #[derive(Debug, Default, Deserialize, Serialize)]
 #[serde(default)]
 pub struct Config {
@@ -11,6 +14,29 @@ pub struct Config {
     pub default_format: Option<String>,
     pub default_model: Option<String>,
     pub whisper_model: Option<String>,
+    pub temp_dir: Option<PathBuf>,
+    /// Spending cap in USD for paid transcription in a single run
+    pub max_cost_per_run: Option<f64>,
+    /// Project that paid API calls are attributed to in the usage ledger
+    pub project: Option<String>,
+    /// Ordered fetch tiers; empty means cache, captions, then Whisper
+    pub tiers: Vec<Tier>,
+    /// Transcript tokens per summarization request, by model prefix;
+    /// longer transcripts are summarized in parts
+    pub summary_chunk_tokens: BTreeMap<String, usize>,
+    /// Provider for model names given without a `provider:` prefix
+    /// (anthropic, openai, azure, gemini, mistral or ollama)
+    pub llm_provider: Option<String>,
+    /// OpenAI-compatible server to use instead of api.openai.com
+    /// (vLLM, llama.cpp server, LM Studio)
+    pub openai_base_url: Option<String>,
+    /// Ollama server (default http://localhost:11434)
+    pub ollama_url: Option<String>,
+    /// Azure OpenAI resource, e.g. https://NAME.openai.azure.com (or set
+    /// AZURE_OPENAI_ENDPOINT); models are named by deployment
+    pub azure_openai_endpoint: Option<String>,
+    /// Azure OpenAI API version (default 2024-10-21)
+    pub azure_openai_api_version: Option<String>,
 }
 
 impl Config {
@@ -29,16 +55,52 @@ impl Config {
     }
 }
 
-pub fn config_path() -> PathBuf {
+fn config_dir() -> PathBuf {
     dirs::config_dir()
         .unwrap_or_else(|| PathBuf::from(".config"))
         .join("ytx")
-        .join("config.toml")
+}
+
+pub fn config_path() -> PathBuf {
+    config_dir().join("config.toml")
+}
+
+/// Terms Whisper should spell correctly, one per line in ~/.config/ytx/glossary.txt
+pub fn glossary_path() -> PathBuf {
+    config_dir().join("glossary.txt")
+}
+
+/// Summarization prompt templates, one `NAME.md` per template
+pub fn prompts_dir() -> PathBuf {
+    config_dir().join("prompts")
+}
+
+/// Load the glossary, or an empty list if there is none
+pub fn load_glossary() -> Vec<String> {
+    let path = glossary_path();
+    match std::fs::read_to_string(&path) {
+        Ok(content) => {
+            debug!("Loading glossary from {}", path.display());
+            parse_glossary(&content)
+        }
+        Err(_) => Vec::new(),
+    }
+}
+
+/// One term per line; blank lines and `#` comments are ignored
+fn parse_glossary(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::policy::TierSource;
 
     #[test]
     fn test_parse_config() {
@@ -47,12 +109,44 @@ default_lang = "es"
 default_format = "json"
 default_model = "gpt-4o"
 whisper_model = "gpt-4o-transcribe"
+temp_dir = "/var/tmp"
+max_cost_per_run = 2.5
+project = "research"
+llm_provider = "ollama"
+openai_base_url = "http://localhost:1234/v1"
+ollama_url = "http://gpu-box:11434"
+azure_openai_endpoint = "https://acme.openai.azure.com"
+
+[summary_chunk_tokens]
+claude = 50000
+"gpt-4o-mini" = 20000
+
+[[tiers]]
+source = "captions"
+
+[[tiers]]
+source = "whisper"
+max_duration_mins = 60
 "#;
         let config: Config = toml::from_str(toml_str).unwrap();
         assert_eq!(config.default_lang.as_deref(), Some("es"));
         assert_eq!(config.default_format.as_deref(), Some("json"));
         assert_eq!(config.default_model.as_deref(), Some("gpt-4o"));
         assert_eq!(config.whisper_model.as_deref(), Some("gpt-4o-transcribe"));
+        assert_eq!(config.temp_dir, Some(PathBuf::from("/var/tmp")));
+        assert_eq!(config.max_cost_per_run, Some(2.5));
+        assert_eq!(config.project.as_deref(), Some("research"));
+        assert_eq!(config.summary_chunk_tokens["gpt-4o-mini"], 20000);
+        assert_eq!(config.llm_provider.as_deref(), Some("ollama"));
+        assert_eq!(config.openai_base_url.as_deref(), Some("http://localhost:1234/v1"));
+        assert_eq!(config.ollama_url.as_deref(), Some("http://gpu-box:11434"));
+        assert_eq!(
+            config.azure_openai_endpoint.as_deref(),
+            Some("https://acme.openai.azure.com")
+        );
+        assert_eq!(config.tiers.len(), 2);
+        assert_eq!(config.tiers[1].source, TierSource::Whisper);
+        assert_eq!(config.tiers[1].max_duration_mins, Some(60.0));
     }
 
     #[test]
@@ -61,6 +155,13 @@ whisper_model = "gpt-4o-transcribe"
         let config: Config = toml::from_str(toml_str).unwrap();
         assert!(config.default_lang.is_none());
         assert!(config.default_format.is_none());
+        assert!(config.tiers.is_empty());
+    }
+
+    #[test]
+    fn test_parse_glossary() {
+        let content = "# product names\nKubernetes\n\n  gRPC  \nScott Aidler\n";
+        assert_eq!(parse_glossary(content), vec!["Kubernetes", "gRPC", "Scott Aidler"]);
     }
 
     #[test]
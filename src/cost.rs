@@ -0,0 +1,179 @@
+use crate::llm::TokenUsage;
+use crate::whisper::WhisperModel;
+
+/// Transcription price in USD per minute of audio
+pub fn whisper_price_per_minute(model: &WhisperModel) -> f64 {
+    match model {
+        WhisperModel::Gpt4oMiniTranscribe => 0.003,
+        WhisperModel::Gpt4oTranscribe => 0.006,
+        WhisperModel::Gpt4oTranscribeDiarize => 0.006,
+        WhisperModel::Whisper1 => 0.006,
+    }
+}
+
+/// Expected cost in USD of transcribing `duration_secs` of audio
+pub fn estimate_whisper(model: &WhisperModel, duration_secs: f64) -> f64 {
+    duration_secs / 60.0 * whisper_price_per_minute(model)
+}
+
+/// LLM prices in USD per million (input, output) tokens, matched by model
+/// name prefix; more specific prefixes come first
+const LLM_PRICES: &[(&str, f64, f64)] = &[
+    ("claude-opus-4-5", 5.0, 25.0),
+    ("claude-opus-4-6", 5.0, 25.0),
+    ("claude-opus", 15.0, 75.0),
+    ("claude-3-opus", 15.0, 75.0),
+    ("claude-sonnet", 3.0, 15.0),
+    ("claude-3-5-sonnet", 3.0, 15.0),
+    ("claude-3-7-sonnet", 3.0, 15.0),
+    ("claude-haiku-4", 1.0, 5.0),
+    ("claude-3-5-haiku", 0.8, 4.0),
+    ("claude-3-haiku", 0.25, 1.25),
+    ("gpt-4o-mini", 0.15, 0.6),
+    ("gpt-4o", 2.5, 10.0),
+    ("gpt-4.1-nano", 0.1, 0.4),
+    ("gpt-4.1-mini", 0.4, 1.6),
+    ("gpt-4.1", 2.0, 8.0),
+    ("gpt-5-nano", 0.05, 0.4),
+    ("gpt-5-mini", 0.25, 2.0),
+    ("gpt-5", 1.25, 10.0),
+    ("o4-mini", 1.1, 4.4),
+    ("o3-mini", 1.1, 4.4),
+    ("o3", 2.0, 8.0),
+    ("gemini-2.5-pro", 1.25, 10.0),
+    ("gemini-2.5-flash-lite", 0.1, 0.4),
+    ("gemini-2.5-flash", 0.3, 2.5),
+    ("gemini-2.0-flash", 0.1, 0.4),
+    ("mistral-large", 2.0, 6.0),
+    ("mistral-medium", 0.4, 2.0),
+    ("mistral-small", 0.1, 0.3),
+];
+
+/// Price in USD per million (input, output) tokens, if the model is known
+pub fn llm_price_per_million(model: &str) -> Option<(f64, f64)> {
+    LLM_PRICES
+        .iter()
+        .find(|(prefix, _, _)| model.starts_with(prefix))
+        .map(|&(_, input, output)| (input, output))
+}
+
+/// Cost in USD of an LLM call; unknown models are counted as free
+pub fn estimate_llm(model: &str, input_tokens: u64, output_tokens: u64) -> f64 {
+    llm_price_per_million(model)
+        .map(|(input, output)| (input_tokens as f64 * input + output_tokens as f64 * output) / 1_000_000.0)
+        .unwrap_or_default()
+}
+
+/// Cost in USD of an LLM call's tokens, with prompt cache writes at 1.25
+/// times the input price and reads at a tenth, as Anthropic bills them
+pub fn estimate_llm_usage(model: &str, usage: &TokenUsage) -> f64 {
+    let cached = usage.cache_write_tokens as f64 * 1.25 + usage.cache_read_tokens as f64 * 0.1;
+    llm_price_per_million(model)
+        .map(|(input, output)| {
+            ((usage.input_tokens as f64 + cached) * input + usage.output_tokens as f64 * output) / 1_000_000.0
+        })
+        .unwrap_or_default()
+}
+
+/// Format a USD amount, keeping precision for sub-cent figures
+pub fn format_usd(amount: f64) -> String {
+    if amount < 0.01 && amount > 0.0 {
+        format!("${amount:.4}")
+    } else {
+        format!("${amount:.2}")
+    }
+}
+
+/// Running total of paid work in this invocation, against an optional cap
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    cap: Option<f64>,
+    spent: f64,
+}
+
+impl Budget {
+    pub fn new(cap: Option<f64>) -> Self {
+        Self { cap, spent: 0.0 }
+    }
+
+    pub fn cap(&self) -> Option<f64> {
+        self.cap
+    }
+
+    pub fn spent(&self) -> f64 {
+        self.spent
+    }
+
+    /// Money left before the cap is reached (`None` if uncapped)
+    pub fn remaining(&self) -> Option<f64> {
+        self.cap.map(|cap| (cap - self.spent).max(0.0))
+    }
+
+    /// Whether spending `estimate` more stays within the cap
+    pub fn allows(&self, estimate: f64) -> bool {
+        self.remaining().is_none_or(|left| estimate <= left)
+    }
+
+    pub fn record(&mut self, cost: f64) {
+        self.spent += cost;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_whisper() {
+        let hour = 3600.0;
+        assert!((estimate_whisper(&WhisperModel::Whisper1, hour) - 0.36).abs() < 1e-9);
+        assert!((estimate_whisper(&WhisperModel::Gpt4oMiniTranscribe, hour) - 0.18).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_estimate_llm() {
+        assert!((estimate_llm("claude-sonnet-4-6", 1_000_000, 100_000) - 4.5).abs() < 1e-9);
+        assert_eq!(llm_price_per_million("gpt-4o-mini-2024-07-18"), Some((0.15, 0.6)));
+        assert_eq!(llm_price_per_million("claude-opus-4-6"), Some((5.0, 25.0)));
+        assert_eq!(llm_price_per_million("o3-mini-2025-01-31"), Some((1.1, 4.4)));
+        assert_eq!(llm_price_per_million("o3-2025-04-16"), Some((2.0, 8.0)));
+        assert_eq!(estimate_llm("llama3", 1000, 1000), 0.0);
+    }
+
+    #[test]
+    fn test_estimate_llm_usage() {
+        let usage = TokenUsage {
+            input_tokens: 100_000,
+            output_tokens: 0,
+            cache_write_tokens: 400_000,
+            cache_read_tokens: 500_000,
+        };
+        // $3/M: 100k full, 400k at 1.25x, 500k at 0.1x = 650k input-equivalent
+        assert!((estimate_llm_usage("claude-sonnet-4-6", &usage) - 1.95).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_budget() {
+        let mut budget = Budget::new(Some(1.0));
+        assert!(budget.allows(0.6));
+        budget.record(0.6);
+        assert!((budget.remaining().unwrap() - 0.4).abs() < 1e-9);
+        assert!(!budget.allows(0.5));
+        assert!(budget.allows(0.4));
+    }
+
+    #[test]
+    fn test_uncapped_budget() {
+        let mut budget = Budget::new(None);
+        budget.record(100.0);
+        assert!(budget.allows(1_000.0));
+        assert!(budget.remaining().is_none());
+    }
+
+    #[test]
+    fn test_format_usd() {
+        assert_eq!(format_usd(1.234), "$1.23");
+        assert_eq!(format_usd(0.0042), "$0.0042");
+        assert_eq!(format_usd(0.0), "$0.00");
+    }
+}
@@ -1,25 +1,64 @@
+pub mod ask;
 pub mod cache;
+pub mod cite;
 pub mod config;
+pub mod cost;
+pub mod llm;
 pub mod output;
+pub mod policy;
+pub mod prompt;
+pub mod sse;
+pub mod structured;
 pub mod summarize;
+pub mod usage;
 pub mod whisper;
+pub mod workspace;
 pub mod youtube;
 
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use eyre::{Result, bail};
+use log::debug;
 use serde::{Deserialize, Serialize};
 
+/// Language value that asks for the spoken language to be detected
+pub const AUTO_LANG: &str = "auto";
+
 /// A single captioned segment
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Segment {
     pub text: String,
     pub start: f64,
     pub duration: f64,
+    /// Speaker label from a diarizing backend (e.g. "A"), if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+    /// Timing was estimated rather than reported by the transcription model
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub estimated: bool,
+    /// Kept, but looks like a transcription model hallucination
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub suspect: bool,
 }
 
 /// Source of the transcript
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TranscriptSource {
     Caption,
+    /// OpenAI's transcription API
     Whisper,
+    /// whisper.cpp, run on this machine
+    Local,
+}
+
+/// A titled section of a video, as marked by the uploader
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Chapter {
+    pub title: String,
+    /// Start in seconds
+    pub start_time: f64,
 }
 
 /// Complete transcript for a video
@@ -28,8 +67,58 @@ pub struct Transcript {
     pub video_id: String,
     pub title: String,
     pub language: String,
+    /// Spoken language, when `language` is a translation of it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_language: Option<String>,
     pub source: TranscriptSource,
     pub segments: Vec<Segment>,
+    /// Display names for speaker labels, e.g. "A" -> "Alice"
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub speakers: BTreeMap<String, String>,
+    /// Hallucination checks on a Whisper transcript
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<Quality>,
+}
+
+/// How trustworthy a Whisper transcript looks
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Quality {
+    /// From 0 to 1: the share of segments with no problems, scaled by the
+    /// model's average token probability where reported
+    pub score: f64,
+    /// Segments removed as hallucinations over silence
+    pub dropped: usize,
+    /// Segments kept but marked `suspect`
+    pub flagged: usize,
+    /// Repetition loops collapsed to a single copy
+    pub collapsed: usize,
+}
+
+impl Transcript {
+    /// Name to show for a speaker label: the mapped name, or `SPEAKER <label>`
+    pub fn speaker_name(&self, label: &str) -> String {
+        self.speakers
+            .get(label)
+            .cloned()
+            .unwrap_or_else(|| format!("SPEAKER {label}"))
+    }
+}
+
+#[cfg(test)]
+impl Transcript {
+    /// An English caption transcript of `segments`, for tests
+    pub(crate) fn for_test(video_id: &str, title: &str, segments: Vec<Segment>) -> Self {
+        Self {
+            video_id: video_id.to_string(),
+            title: title.to_string(),
+            language: "en".to_string(),
+            source_language: None,
+            source: TranscriptSource::Caption,
+            segments,
+            speakers: Default::default(),
+            quality: None,
+        }
+    }
 }
 
 impl std::fmt::Display for TranscriptSource {
@@ -37,8 +126,79 @@ impl std::fmt::Display for TranscriptSource {
         match self {
             TranscriptSource::Caption => write!(f, "caption"),
             TranscriptSource::Whisper => write!(f, "whisper"),
+            TranscriptSource::Local => write!(f, "local"),
+        }
+    }
+}
+
+/// Retry an async operation with exponential backoff
+pub async fn retry<F, Fut, T>(max_attempts: u32, operation: F) -> Result<T>
+where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = Result<T>>,
+{
+    let mut last_err = None;
+    for attempt in 0..max_attempts {
+        match operation().await {
+            Ok(val) => return Ok(val),
+            Err(e) => {
+                if attempt + 1 < max_attempts {
+                    let delay = Duration::from_millis(500 * 2u64.pow(attempt));
+                    debug!("Attempt {} failed: {e}, retrying in {delay:?}", attempt + 1);
+                    tokio::time::sleep(delay).await;
+                }
+                last_err = Some(e);
+            }
         }
     }
+    Err(last_err.unwrap())
+}
+
+/// An error from work that had already paid for some requests, carrying
+/// what they used so the caller can still record it
+#[derive(Debug)]
+pub struct PaidFailure<T> {
+    pub spent: T,
+    error: eyre::Report,
+}
+
+impl<T: std::fmt::Debug + Send + Sync + 'static> PaidFailure<T> {
+    pub fn wrap(error: eyre::Report, spent: T) -> eyre::Report {
+        eyre::Report::new(Self { spent, error })
+    }
+
+    /// What a failed call spent before failing, if it reported any
+    pub fn spent(report: &eyre::Report) -> Option<&T> {
+        report.downcast_ref::<Self>().map(|failure| &failure.spent)
+    }
+}
+
+impl<T> std::fmt::Display for PaidFailure<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl<T: std::fmt::Debug> std::error::Error for PaidFailure<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// Parse a speaker name mapping such as `A=Alice,B=Bob`
+pub fn parse_speaker_names(input: &str) -> Result<BTreeMap<String, String>> {
+    let mut names = BTreeMap::new();
+    for pair in input.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let Some((label, name)) = pair.split_once('=') else {
+            bail!("invalid speaker mapping '{pair}', expected LABEL=NAME");
+        };
+        let (label, name) = (label.trim(), name.trim());
+        if label.is_empty() || name.is_empty() {
+            bail!("invalid speaker mapping '{pair}', expected LABEL=NAME");
+        }
+        names.insert(label.to_string(), name.to_string());
+    }
+    Ok(names)
 }
 
 /// Extract video ID from various YouTube URL formats
@@ -144,8 +304,51 @@ mod tests {
         assert_eq!(extract_video_id(""), None);
     }
 
+    #[test]
+    fn test_parse_speaker_names() {
+        let names = parse_speaker_names("A=Alice, B = Bob").unwrap();
+        assert_eq!(names.get("A").map(String::as_str), Some("Alice"));
+        assert_eq!(names.get("B").map(String::as_str), Some("Bob"));
+        assert!(parse_speaker_names("A").is_err());
+        assert!(parse_speaker_names("=Alice").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retry_recovers_after_failure() {
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let result = retry(3, || async {
+            match attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                0 => eyre::bail!("transient"),
+                n => Ok(n),
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let result: Result<()> = retry(1, || async { eyre::bail!("permanent") }).await;
+        assert_eq!(result.unwrap_err().to_string(), "permanent");
+    }
+
     #[test]
     fn test_whitespace_trimming() {
         assert_eq!(extract_video_id("  dQw4w9WgXcQ  "), Some("dQw4w9WgXcQ".to_string()));
     }
+
+    #[test]
+    fn test_local_source_is_not_whisper_api() {
+        assert_eq!(TranscriptSource::Local.to_string(), "local");
+        assert_eq!(serde_json::to_value(TranscriptSource::Local).unwrap(), "Local");
+        assert_ne!(TranscriptSource::Local, TranscriptSource::Whisper);
+    }
+
+    #[test]
+    fn test_paid_failure() {
+        let report = PaidFailure::wrap(eyre::eyre!("chunk 3 failed"), 120.5);
+        assert_eq!(report.to_string(), "chunk 3 failed");
+        assert_eq!(PaidFailure::<f64>::spent(&report), Some(&120.5));
+        assert_eq!(PaidFailure::<f64>::spent(&eyre::eyre!("offline")), None);
+    }
 }
@@ -0,0 +1,1300 @@
+use std::io::Write;
+
+use eyre::{Result, bail};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::sse::SseParser;
+use crate::summarize::estimate_tokens;
+
+/// Longest reply asked for, which the context must also leave room for
+pub const REPLY_TOKENS: usize = 4096;
+
+const ANTHROPIC_URL: &str = "https://api.anthropic.com/v1";
+const OPENAI_URL: &str = "https://api.openai.com/v1";
+const GEMINI_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+const MISTRAL_URL: &str = "https://api.mistral.ai/v1";
+const OLLAMA_URL: &str = "http://localhost:11434";
+
+/// Azure OpenAI data-plane API version used unless config names another
+const AZURE_API_VERSION: &str = "2024-10-21";
+
+/// Service that runs a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    Anthropic,
+    /// OpenAI, or any server with an OpenAI-compatible chat completions API
+    OpenAi,
+    /// A model deployed to an Azure OpenAI resource, named by its deployment
+    Azure,
+    Gemini,
+    Mistral,
+    Ollama,
+}
+
+impl Provider {
+    const ALL: [Provider; 6] = [
+        Provider::Anthropic,
+        Provider::OpenAi,
+        Provider::Azure,
+        Provider::Gemini,
+        Provider::Mistral,
+        Provider::Ollama,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Provider::Anthropic => "anthropic",
+            Provider::OpenAi => "openai",
+            Provider::Azure => "azure",
+            Provider::Gemini => "gemini",
+            Provider::Mistral => "mistral",
+            Provider::Ollama => "ollama",
+        }
+    }
+
+    /// Guess from the model name, defaulting to OpenAI
+    fn infer(model: &str) -> Self {
+        if model.starts_with("claude") {
+            Provider::Anthropic
+        } else if model.starts_with("gemini") {
+            Provider::Gemini
+        } else if ["mistral", "ministral", "magistral", "codestral"]
+            .iter()
+            .any(|prefix| model.starts_with(prefix))
+        {
+            Provider::Mistral
+        } else {
+            Provider::OpenAi
+        }
+    }
+
+    /// The provider's API, at the address configured for it
+    pub fn api(self, config: &Config) -> Result<Box<dyn LlmProvider>> {
+        let url = |configured: &Option<String>, default: &str| {
+            configured
+                .as_deref()
+                .unwrap_or(default)
+                .trim_end_matches('/')
+                .to_string()
+        };
+        Ok(match self {
+            Provider::Anthropic => Box::new(Anthropic {
+                base_url: ANTHROPIC_URL.to_string(),
+            }),
+            Provider::OpenAi => {
+                let base_url = url(&config.openai_base_url, OPENAI_URL);
+                Box::new(OpenAi {
+                    name: "OpenAI API",
+                    key_var: "OPENAI_API_KEY",
+                    // Self-hosted OpenAI-compatible servers usually want no key
+                    key_required: base_url == OPENAI_URL,
+                    base_url,
+                })
+            }
+            Provider::Mistral => Box::new(OpenAi {
+                name: "Mistral API",
+                key_var: "MISTRAL_API_KEY",
+                key_required: true,
+                base_url: MISTRAL_URL.to_string(),
+            }),
+            Provider::Azure => {
+                let endpoint = config
+                    .azure_openai_endpoint
+                    .clone()
+                    .or_else(|| std::env::var("AZURE_OPENAI_ENDPOINT").ok())
+                    .ok_or_else(|| {
+                        eyre::eyre!("Azure OpenAI needs azure_openai_endpoint in config or AZURE_OPENAI_ENDPOINT set")
+                    })?;
+                Box::new(Azure {
+                    endpoint: endpoint.trim_end_matches('/').to_string(),
+                    api_version: config
+                        .azure_openai_api_version
+                        .clone()
+                        .unwrap_or_else(|| AZURE_API_VERSION.to_string()),
+                })
+            }
+            Provider::Gemini => Box::new(Gemini {
+                base_url: GEMINI_URL.to_string(),
+            }),
+            Provider::Ollama => Box::new(Ollama {
+                base_url: url(&config.ollama_url, OLLAMA_URL),
+            }),
+        })
+    }
+}
+
+impl std::str::FromStr for Provider {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Self::ALL.into_iter().find(|p| p.name() == s) {
+            Some(provider) => Ok(provider),
+            None => bail!(
+                "unknown LLM provider '{s}' (expected one of: {})",
+                Self::ALL.map(Provider::name).join(", ")
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Tokens billed for an LLM call, as reported by the provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    /// Prompt tokens billed at the full rate
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Prompt tokens written to the provider's prompt cache
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub cache_write_tokens: u64,
+    /// Prompt tokens read back from the prompt cache
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub cache_read_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Every prompt token, cached or not
+    pub fn prompt_tokens(&self) -> u64 {
+        self.input_tokens + self.cache_write_tokens + self.cache_read_tokens
+    }
+}
+
+impl std::ops::AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_write_tokens += other.cache_write_tokens;
+        self.cache_read_tokens += other.cache_read_tokens;
+    }
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+/// Who a message in a conversation is from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Message {
+    pub role: Role,
+    pub content: String,
+}
+
+impl Message {
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            content: content.into(),
+        }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: Role::Assistant,
+            content: content.into(),
+        }
+    }
+}
+
+/// Everything sent to ask for one reply
+#[derive(Debug, Clone, Copy)]
+pub struct Chat<'a> {
+    pub system: &'a str,
+    /// Long material that stays the same from call to call, such as a
+    /// transcript. It follows the system prompt and is marked for caching
+    /// where the API needs that; others cache a repeated prefix themselves.
+    pub context: Option<&'a str>,
+    /// The conversation so far, ending with the user's turn
+    pub messages: &'a [Message],
+}
+
+impl Chat<'_> {
+    /// The system prompt with the context after it, for APIs that take a
+    /// single system text
+    fn system_text(&self) -> String {
+        match self.context {
+            Some(context) => format!("{}\n\n{context}", self.system),
+            None => self.system.to_string(),
+        }
+    }
+}
+
+/// The form a reply is asked for in
+#[derive(Debug, Clone, Copy)]
+pub enum Reply<'a> {
+    Text,
+    /// Text sent piece by piece as it's generated
+    Stream,
+    /// A JSON object matching the schema, by tool call or JSON mode where
+    /// the API has one; the prompt should describe the schema too
+    Json(&'a Value),
+}
+
+impl Reply<'_> {
+    fn is_stream(self) -> bool {
+        matches!(self, Reply::Stream)
+    }
+}
+
+/// One provider's chat API: how to ask it for a reply and read what comes
+/// back. Sending, status checks and streaming are shared.
+pub trait LlmProvider {
+    /// How the API is named in errors
+    fn name(&self) -> &'static str;
+
+    /// Where requests go, for display
+    fn base_url(&self) -> &str;
+
+    /// A request for the next reply in a chat
+    fn request(
+        &self,
+        client: &reqwest::Client,
+        model: &str,
+        chat: &Chat,
+        reply: Reply,
+    ) -> Result<reqwest::RequestBuilder>;
+
+    /// Text of a whole reply
+    fn text(&self, json: &Value) -> Result<String>;
+
+    /// Tokens billed for a whole reply
+    fn usage(&self, json: &Value) -> TokenUsage;
+
+    /// Text from one event of a streamed reply, noting token counts in
+    /// `usage` as they're reported
+    fn stream_event(&self, event: &Value, usage: &mut TokenUsage) -> Result<Option<String>>;
+
+    /// How a streamed reply is framed
+    fn stream_parser(&self) -> SseParser {
+        SseParser::default()
+    }
+
+    /// A request listing the models the API offers
+    fn models_request(&self, _client: &reqwest::Client) -> Result<reqwest::RequestBuilder> {
+        bail!("{} can't list its models", self.name())
+    }
+
+    /// Model names from the listing, by default OpenAI-style `data[].id`
+    fn models(&self, json: &Value) -> Vec<String> {
+        json["data"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|m| m["id"].as_str().map(str::to_string))
+            .collect()
+    }
+}
+
+/// A model and the API that serves it
+pub struct Llm {
+    pub provider: Provider,
+    pub model: String,
+    api: Box<dyn LlmProvider>,
+}
+
+impl Llm {
+    /// Resolve `provider:model`, or a bare model name served by the
+    /// configured provider (or the one its name suggests)
+    pub fn resolve(spec: &str, config: &Config) -> Result<Self> {
+        let (provider, model) = match spec.split_once(':') {
+            // Ollama tags also contain a colon ("llama3.1:8b"), so only a
+            // known provider name counts as a prefix
+            Some((prefix, model)) if prefix.parse::<Provider>().is_ok() => (prefix.parse()?, model),
+            _ => match &config.llm_provider {
+                Some(name) => (name.parse()?, spec),
+                None => (Provider::infer(spec), spec),
+            },
+        };
+        if model.is_empty() {
+            bail!("no model given in '{spec}'");
+        }
+
+        Ok(Self {
+            provider,
+            model: model.to_string(),
+            api: provider.api(config)?,
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        self.api.base_url()
+    }
+
+    /// Whether the model runs on hardware we own, so calls cost nothing
+    pub fn is_local(&self) -> bool {
+        self.provider == Provider::Ollama
+    }
+
+    /// Send one message, returning the reply and the tokens it cost. A
+    /// streamed reply is echoed to stdout as it arrives; a JSON one is
+    /// returned as JSON text.
+    pub async fn complete(
+        &self,
+        client: &reqwest::Client,
+        system: &str,
+        user: &str,
+        reply: Reply<'_>,
+    ) -> Result<(String, TokenUsage)> {
+        let messages = [Message::user(user)];
+        let chat = Chat {
+            system,
+            context: None,
+            messages: &messages,
+        };
+        self.chat(client, &chat, reply).await
+    }
+
+    /// Ask for the next reply in a chat, as `complete` does for one message
+    pub async fn chat(
+        &self,
+        client: &reqwest::Client,
+        chat: &Chat<'_>,
+        reply: Reply<'_>,
+    ) -> Result<(String, TokenUsage)> {
+        let api = self.api.as_ref();
+        debug!("Calling {} at {} with model {}", api.name(), api.base_url(), self.model);
+
+        let resp = send(api, api.request(client, &self.model, chat, reply)?).await?;
+
+        if reply.is_stream() {
+            let mut usage = TokenUsage::default();
+            let text = read_stream(resp, api.stream_parser(), |event| api.stream_event(event, &mut usage)).await?;
+            return Ok((text, usage));
+        }
+
+        let json: Value = resp.json().await?;
+        Ok((api.text(&json)?, api.usage(&json)))
+    }
+}
+
+impl std::fmt::Display for Llm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.provider, self.model)
+    }
+}
+
+impl std::fmt::Debug for Llm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Llm({self} at {})", self.base_url())
+    }
+}
+
+/// Names of the models an API offers, sorted
+pub async fn list_models(client: &reqwest::Client, api: &dyn LlmProvider) -> Result<Vec<String>> {
+    let resp = send(api, api.models_request(client)?).await?;
+    let json: Value = resp.json().await?;
+    let mut models = api.models(&json);
+    models.sort();
+    Ok(models)
+}
+
+async fn send(api: &dyn LlmProvider, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+    let resp = request
+        .send()
+        .await
+        .map_err(|e| eyre::eyre!("could not reach {} at {}: {e}", api.name(), api.base_url()))?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        bail!("{} returned {status}: {body}", api.name());
+    }
+    Ok(resp)
+}
+
+/// Read a streamed reply, echoing each piece of text that `parse` finds in
+/// an event to stdout and returning all of it
+async fn read_stream(
+    mut resp: reqwest::Response,
+    mut parser: SseParser,
+    mut parse: impl FnMut(&Value) -> Result<Option<String>>,
+) -> Result<String> {
+    let mut text = String::new();
+    let mut stdout = std::io::stdout();
+
+    loop {
+        let chunk = resp.chunk().await?;
+        let events = match &chunk {
+            Some(bytes) => parser.push(bytes),
+            None => parser.finish().into_iter().collect(),
+        };
+
+        for data in events {
+            // OpenAI ends the stream with a sentinel rather than JSON
+            if data == "[DONE]" {
+                continue;
+            }
+            let json: Value = serde_json::from_str(&data)?;
+            if let Some(delta) = parse(&json)? {
+                stdout.write_all(delta.as_bytes())?;
+                stdout.flush()?;
+                text.push_str(&delta);
+            }
+        }
+
+        if chunk.is_none() {
+            break;
+        }
+    }
+
+    if text.is_empty() {
+        bail!("stream ended without any text");
+    }
+    Ok(text)
+}
+
+fn api_key(var: &str, purpose: &str) -> Result<String> {
+    std::env::var(var).map_err(|_| eyre::eyre!("{var} environment variable not set (required for {purpose})"))
+}
+
+struct Anthropic {
+    base_url: String,
+}
+
+impl LlmProvider for Anthropic {
+    fn name(&self) -> &'static str {
+        "Anthropic API"
+    }
+
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn request(
+        &self,
+        client: &reqwest::Client,
+        model: &str,
+        chat: &Chat,
+        reply: Reply,
+    ) -> Result<reqwest::RequestBuilder> {
+        let api_key = api_key("ANTHROPIC_API_KEY", "Claude models")?;
+        let mut body = serde_json::json!({
+            "model": model,
+            "max_tokens": REPLY_TOKENS,
+            "system": anthropic_system(chat),
+            "messages": chat.messages,
+            "stream": reply.is_stream()
+        });
+        if let Reply::Json(schema) = reply {
+            // Claude has no JSON mode, but a forced tool call's input
+            // always matches the tool's schema
+            body["tools"] = serde_json::json!([{
+                "name": "reply",
+                "description": "Give the reply",
+                "input_schema": schema
+            }]);
+            body["tool_choice"] = serde_json::json!({"type": "tool", "name": "reply"});
+        }
+        Ok(client
+            .post(format!("{}/messages", self.base_url))
+            .header("x-api-key", api_key)
+            .header("anthropic-version", "2023-06-01")
+            .json(&body))
+    }
+
+    fn text(&self, json: &Value) -> Result<String> {
+        extract_anthropic_text(json)
+    }
+
+    fn usage(&self, json: &Value) -> TokenUsage {
+        extract_anthropic_usage(json)
+    }
+
+    fn stream_event(&self, event: &Value, usage: &mut TokenUsage) -> Result<Option<String>> {
+        anthropic_stream_event(event, usage)
+    }
+
+    fn models_request(&self, client: &reqwest::Client) -> Result<reqwest::RequestBuilder> {
+        Ok(client
+            .get(format!("{}/models?limit=1000", self.base_url))
+            .header("x-api-key", api_key("ANTHROPIC_API_KEY", "Claude models")?)
+            .header("anthropic-version", "2023-06-01"))
+    }
+}
+
+/// The system prompt, with the context in a block of its own marked for
+/// caching. Claude only caches prefixes it's told to; a cached read costs a
+/// tenth of the input price, so each follow-up pays little for the context.
+fn anthropic_system(chat: &Chat) -> Value {
+    match chat.context {
+        Some(context) => serde_json::json!([
+            {"type": "text", "text": chat.system},
+            {"type": "text", "text": context, "cache_control": {"type": "ephemeral"}}
+        ]),
+        None => chat.system.into(),
+    }
+}
+
+/// OpenAI's chat completions API, which Mistral and self-hosted servers
+/// also speak
+struct OpenAi {
+    name: &'static str,
+    base_url: String,
+    key_var: &'static str,
+    key_required: bool,
+}
+
+impl OpenAi {
+    fn key(&self) -> Result<Option<String>> {
+        match std::env::var(self.key_var) {
+            Ok(key) => Ok(Some(key)),
+            Err(_) if !self.key_required => Ok(None),
+            Err(_) => bail!(
+                "{} environment variable not set (required for the {})",
+                self.key_var,
+                self.name
+            ),
+        }
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> Result<reqwest::RequestBuilder> {
+        Ok(match self.key()? {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        })
+    }
+}
+
+impl LlmProvider for OpenAi {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn request(
+        &self,
+        client: &reqwest::Client,
+        model: &str,
+        chat: &Chat,
+        reply: Reply,
+    ) -> Result<reqwest::RequestBuilder> {
+        let request = client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(&openai_body(model, chat, reply));
+        self.authorize(request)
+    }
+
+    fn text(&self, json: &Value) -> Result<String> {
+        extract_openai_text(json)
+    }
+
+    fn usage(&self, json: &Value) -> TokenUsage {
+        extract_openai_usage(json)
+    }
+
+    fn stream_event(&self, event: &Value, usage: &mut TokenUsage) -> Result<Option<String>> {
+        Ok(openai_stream_event(event, usage))
+    }
+
+    fn models_request(&self, client: &reqwest::Client) -> Result<reqwest::RequestBuilder> {
+        self.authorize(client.get(format!("{}/models", self.base_url)))
+    }
+}
+
+/// An Azure OpenAI resource, where the model is the name of a deployment
+struct Azure {
+    endpoint: String,
+    api_version: String,
+}
+
+impl LlmProvider for Azure {
+    fn name(&self) -> &'static str {
+        "Azure OpenAI"
+    }
+
+    fn base_url(&self) -> &str {
+        &self.endpoint
+    }
+
+    fn request(
+        &self,
+        client: &reqwest::Client,
+        deployment: &str,
+        chat: &Chat,
+        reply: Reply,
+    ) -> Result<reqwest::RequestBuilder> {
+        let api_key = api_key("AZURE_OPENAI_API_KEY", "Azure OpenAI")?;
+        Ok(client
+            .post(format!(
+                "{}/openai/deployments/{deployment}/chat/completions?api-version={}",
+                self.endpoint, self.api_version
+            ))
+            .header("api-key", api_key)
+            .json(&openai_body(deployment, chat, reply)))
+    }
+
+    fn text(&self, json: &Value) -> Result<String> {
+        extract_openai_text(json)
+    }
+
+    fn usage(&self, json: &Value) -> TokenUsage {
+        extract_openai_usage(json)
+    }
+
+    fn stream_event(&self, event: &Value, usage: &mut TokenUsage) -> Result<Option<String>> {
+        Ok(openai_stream_event(event, usage))
+    }
+
+    fn models_request(&self, _client: &reqwest::Client) -> Result<reqwest::RequestBuilder> {
+        // Only the Azure management API lists deployments, and it wants an
+        // Azure AD login rather than the resource key
+        bail!("Azure OpenAI deployments are listed in the Azure portal, not by ytx")
+    }
+}
+
+fn openai_body(model: &str, chat: &Chat, reply: Reply) -> Value {
+    let mut body = serde_json::json!({
+        "model": model,
+        "messages": with_system(chat)
+    });
+    match reply {
+        Reply::Text => {}
+        Reply::Stream => {
+            // Token counts only come in a streamed reply when asked for
+            body["stream"] = true.into();
+            body["stream_options"] = serde_json::json!({"include_usage": true});
+        }
+        Reply::Json(schema) => {
+            body["response_format"] = serde_json::json!({
+                "type": "json_schema",
+                "json_schema": {"name": "reply", "strict": true, "schema": schema}
+            });
+        }
+    }
+    body
+}
+
+/// The chat's messages after a system message, as OpenAI and Ollama take
+/// them. OpenAI caches a long repeated prefix without being asked.
+fn with_system(chat: &Chat) -> Value {
+    let mut messages = vec![serde_json::json!({"role": "system", "content": chat.system_text()})];
+    messages.extend(chat.messages.iter().map(|m| serde_json::json!(m)));
+    messages.into()
+}
+
+struct Gemini {
+    base_url: String,
+}
+
+impl LlmProvider for Gemini {
+    fn name(&self) -> &'static str {
+        "Gemini API"
+    }
+
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn request(
+        &self,
+        client: &reqwest::Client,
+        model: &str,
+        chat: &Chat,
+        reply: Reply,
+    ) -> Result<reqwest::RequestBuilder> {
+        let api_key = api_key("GEMINI_API_KEY", "Gemini models")?;
+        let method = if reply.is_stream() {
+            "streamGenerateContent?alt=sse"
+        } else {
+            "generateContent"
+        };
+        let contents: Vec<Value> = chat
+            .messages
+            .iter()
+            .map(|m| {
+                let role = match m.role {
+                    Role::User => "user",
+                    Role::Assistant => "model",
+                };
+                serde_json::json!({"role": role, "parts": [{"text": m.content}]})
+            })
+            .collect();
+        let mut body = serde_json::json!({
+            "systemInstruction": {
+                "parts": [{"text": chat.system_text()}]
+            },
+            "contents": contents
+        });
+        if let Reply::Json(_) = reply {
+            // JSON mode only: Gemini's own schema dialect is an OpenAPI
+            // subset, so the schema is left to the prompt
+            body["generationConfig"] = serde_json::json!({"responseMimeType": "application/json"});
+        }
+        Ok(client
+            .post(format!("{}/models/{model}:{method}", self.base_url))
+            .header("x-goog-api-key", api_key)
+            .json(&body))
+    }
+
+    fn text(&self, json: &Value) -> Result<String> {
+        match gemini_text(json) {
+            Some(text) if !text.is_empty() => Ok(text),
+            _ => match json["promptFeedback"]["blockReason"].as_str() {
+                Some(reason) => bail!("Gemini API blocked the prompt ({reason})"),
+                None => bail!("unexpected Gemini API response format"),
+            },
+        }
+    }
+
+    fn usage(&self, json: &Value) -> TokenUsage {
+        let usage = &json["usageMetadata"];
+        // Thinking is billed as output
+        let output = ["candidatesTokenCount", "thoughtsTokenCount"]
+            .iter()
+            .filter_map(|field| usage[field].as_u64())
+            .sum();
+        TokenUsage {
+            input_tokens: usage["promptTokenCount"].as_u64().unwrap_or_default(),
+            output_tokens: output,
+            ..Default::default()
+        }
+    }
+
+    /// Every chunk is a partial reply, with the running token counts
+    fn stream_event(&self, event: &Value, usage: &mut TokenUsage) -> Result<Option<String>> {
+        if let Some(message) = event["error"]["message"].as_str() {
+            bail!("Gemini API stream failed: {message}");
+        }
+        if event["usageMetadata"].is_object() {
+            *usage = self.usage(event);
+        }
+        Ok(gemini_text(event))
+    }
+
+    fn models_request(&self, client: &reqwest::Client) -> Result<reqwest::RequestBuilder> {
+        Ok(client
+            .get(format!("{}/models?pageSize=1000", self.base_url))
+            .header("x-goog-api-key", api_key("GEMINI_API_KEY", "Gemini models")?))
+    }
+
+    /// Only models that can generate text, without the `models/` prefix
+    fn models(&self, json: &Value) -> Vec<String> {
+        json["models"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|m| {
+                m["supportedGenerationMethods"]
+                    .as_array()
+                    .is_some_and(|methods| methods.iter().any(|method| method == "generateContent"))
+            })
+            .filter_map(|m| m["name"].as_str())
+            .map(|name| name.trim_start_matches("models/").to_string())
+            .collect()
+    }
+}
+
+fn gemini_text(json: &Value) -> Option<String> {
+    let parts = json["candidates"][0]["content"]["parts"].as_array()?;
+    Some(
+        parts
+            .iter()
+            // Thought summaries aren't part of the answer
+            .filter(|part| part["thought"] != true)
+            .filter_map(|part| part["text"].as_str())
+            .collect(),
+    )
+}
+
+struct Ollama {
+    base_url: String,
+}
+
+impl LlmProvider for Ollama {
+    fn name(&self) -> &'static str {
+        "Ollama"
+    }
+
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn request(
+        &self,
+        client: &reqwest::Client,
+        model: &str,
+        chat: &Chat,
+        reply: Reply,
+    ) -> Result<reqwest::RequestBuilder> {
+        // Ollama cuts prompts to a small default context, so size it to fit
+        let prompt: usize = estimate_tokens(&chat.system_text())
+            + chat.messages.iter().map(|m| estimate_tokens(&m.content)).sum::<usize>();
+        let num_ctx = (prompt + REPLY_TOKENS).next_multiple_of(1024);
+
+        let mut body = serde_json::json!({
+            "model": model,
+            "messages": with_system(chat),
+            "stream": reply.is_stream(),
+            "options": {
+                "num_ctx": num_ctx
+            }
+        });
+        if let Reply::Json(schema) = reply {
+            body["format"] = schema.clone();
+        }
+        Ok(client.post(format!("{}/api/chat", self.base_url)).json(&body))
+    }
+
+    fn text(&self, json: &Value) -> Result<String> {
+        match ollama_event(json, &mut TokenUsage::default())? {
+            Some(text) if !text.is_empty() => Ok(text),
+            _ => bail!("unexpected Ollama response format"),
+        }
+    }
+
+    fn usage(&self, json: &Value) -> TokenUsage {
+        let mut usage = TokenUsage::default();
+        // Only errors fail, and `text` has already reported those
+        let _ = ollama_event(json, &mut usage);
+        usage
+    }
+
+    fn stream_event(&self, event: &Value, usage: &mut TokenUsage) -> Result<Option<String>> {
+        ollama_event(event, usage)
+    }
+
+    fn stream_parser(&self) -> SseParser {
+        SseParser::ndjson()
+    }
+
+    fn models_request(&self, client: &reqwest::Client) -> Result<reqwest::RequestBuilder> {
+        Ok(client.get(format!("{}/api/tags", self.base_url)))
+    }
+
+    fn models(&self, json: &Value) -> Vec<String> {
+        json["models"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|m| m["name"].as_str().map(str::to_string))
+            .collect()
+    }
+}
+
+/// Text from an Ollama reply, or one line of a streamed one; the final
+/// line carries the token counts
+fn ollama_event(event: &Value, usage: &mut TokenUsage) -> Result<Option<String>> {
+    if let Some(error) = event["error"].as_str() {
+        bail!("Ollama failed: {error}");
+    }
+    if event["done"] == true {
+        usage.input_tokens = event["prompt_eval_count"].as_u64().unwrap_or_default();
+        usage.output_tokens = event["eval_count"].as_u64().unwrap_or_default();
+    }
+    Ok(event["message"]["content"].as_str().map(str::to_string))
+}
+
+fn extract_anthropic_text(json: &Value) -> Result<String> {
+    if let Some(content) = json.get("content").and_then(|c| c.as_array()) {
+        let text: String = content
+            .iter()
+            .filter_map(|block| {
+                if block.get("type")?.as_str()? == "text" {
+                    block.get("text")?.as_str().map(|s| s.to_string())
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("");
+        if !text.is_empty() {
+            return Ok(text);
+        }
+        // A forced tool call, as asked for JSON replies
+        if let Some(block) = content.iter().find(|block| block["type"] == "tool_use") {
+            return Ok(block["input"].to_string());
+        }
+    }
+    bail!("unexpected Anthropic API response format");
+}
+
+fn extract_openai_text(json: &Value) -> Result<String> {
+    if let Some(text) = json
+        .get("choices")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("message"))
+        .and_then(|m| m.get("content"))
+        .and_then(|t| t.as_str())
+    {
+        return Ok(text.to_string());
+    }
+    bail!("unexpected OpenAI API response format");
+}
+
+/// Text from one streamed Anthropic event, noting token counts as they're
+/// reported: input when the message starts, output when it ends
+fn anthropic_stream_event(event: &Value, usage: &mut TokenUsage) -> Result<Option<String>> {
+    match event["type"].as_str() {
+        Some("message_start") => {
+            *usage = extract_anthropic_usage(&event["message"]);
+        }
+        Some("message_delta") => {
+            usage.output_tokens = event["usage"]["output_tokens"].as_u64().unwrap_or_default();
+        }
+        Some("content_block_delta") if event["delta"]["type"] == "text_delta" => {
+            return Ok(event["delta"]["text"].as_str().map(str::to_string));
+        }
+        Some("error") => bail!("Anthropic API stream failed: {}", event["error"]),
+        _ => {}
+    }
+    Ok(None)
+}
+
+/// Text from one streamed OpenAI chunk; the last chunk carries the usage
+fn openai_stream_event(event: &Value, usage: &mut TokenUsage) -> Option<String> {
+    if event["usage"].is_object() {
+        *usage = extract_openai_usage(event);
+    }
+    event["choices"][0]["delta"]["content"].as_str().map(str::to_string)
+}
+
+fn extract_anthropic_usage(json: &Value) -> TokenUsage {
+    let usage = &json["usage"];
+    TokenUsage {
+        input_tokens: usage["input_tokens"].as_u64().unwrap_or_default(),
+        output_tokens: usage["output_tokens"].as_u64().unwrap_or_default(),
+        cache_write_tokens: usage["cache_creation_input_tokens"].as_u64().unwrap_or_default(),
+        cache_read_tokens: usage["cache_read_input_tokens"].as_u64().unwrap_or_default(),
+    }
+}
+
+fn extract_openai_usage(json: &Value) -> TokenUsage {
+    let usage = &json["usage"];
+    TokenUsage {
+        input_tokens: usage["prompt_tokens"].as_u64().unwrap_or_default(),
+        output_tokens: usage["completion_tokens"].as_u64().unwrap_or_default(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn llm(spec: &str) -> Llm {
+        Llm::resolve(spec, &Config::default()).unwrap()
+    }
+
+    #[test]
+    fn test_infer_provider() {
+        assert_eq!(Provider::infer("claude-sonnet-4-6"), Provider::Anthropic);
+        assert_eq!(Provider::infer("claude-3-opus-20240229"), Provider::Anthropic);
+        assert_eq!(Provider::infer("gpt-4o-mini"), Provider::OpenAi);
+        assert_eq!(Provider::infer("gemini-2.5-flash"), Provider::Gemini);
+        assert_eq!(Provider::infer("mistral-large-latest"), Provider::Mistral);
+        assert_eq!(Provider::infer("llama3"), Provider::OpenAi);
+    }
+
+    #[test]
+    fn test_extract_anthropic_text() {
+        let json = serde_json::json!({
+            "content": [
+                {
+                    "type": "text",
+                    "text": "Here is the summary."
+                }
+            ]
+        });
+        assert_eq!(extract_anthropic_text(&json).unwrap(), "Here is the summary.");
+    }
+
+    #[test]
+    fn test_extract_anthropic_tool_call() {
+        let json = serde_json::json!({
+            "content": [
+                {
+                    "type": "tool_use",
+                    "id": "toolu_01",
+                    "name": "reply",
+                    "input": {"tldr": "Short."}
+                }
+            ],
+            "stop_reason": "tool_use"
+        });
+        assert_eq!(extract_anthropic_text(&json).unwrap(), r#"{"tldr":"Short."}"#);
+    }
+
+    #[test]
+    fn test_openai_json_body() {
+        let schema = serde_json::json!({"type": "object"});
+        let messages = [Message::user("user")];
+        let chat = Chat {
+            system: "system",
+            context: None,
+            messages: &messages,
+        };
+        let body = openai_body("gpt-4o", &chat, Reply::Json(&schema));
+        assert_eq!(body["response_format"]["json_schema"]["schema"], schema);
+        assert!(body.get("stream").is_none());
+    }
+
+    #[test]
+    fn test_chat_context() {
+        let messages = [
+            Message::user("What's the price?"),
+            Message::assistant("$10 [01:02]"),
+            Message::user("Per month?"),
+        ];
+        let chat = Chat {
+            system: "Answer from the transcript.",
+            context: Some("[01:02] It costs ten dollars a month."),
+            messages: &messages,
+        };
+
+        // Claude caches only the block marked for it: the context
+        let system = anthropic_system(&chat);
+        assert_eq!(system[0]["text"], "Answer from the transcript.");
+        assert!(system[0].get("cache_control").is_none());
+        assert_eq!(system[1]["cache_control"]["type"], "ephemeral");
+
+        let messages = with_system(&chat);
+        assert_eq!(messages.as_array().unwrap().len(), 4);
+        assert_eq!(
+            messages[0]["content"],
+            "Answer from the transcript.\n\n[01:02] It costs ten dollars a month."
+        );
+        assert_eq!(
+            messages[2],
+            serde_json::json!({"role": "assistant", "content": "$10 [01:02]"})
+        );
+
+        let plain = Chat { context: None, ..chat };
+        assert_eq!(anthropic_system(&plain), "Answer from the transcript.");
+    }
+
+    #[test]
+    fn test_extract_anthropic_text_empty() {
+        let json = serde_json::json!({"content": []});
+        assert!(extract_anthropic_text(&json).is_err());
+    }
+
+    #[test]
+    fn test_extract_openai_text() {
+        let json = serde_json::json!({
+            "choices": [
+                {
+                    "message": {
+                        "role": "assistant",
+                        "content": "Summary of the video."
+                    }
+                }
+            ]
+        });
+        assert_eq!(extract_openai_text(&json).unwrap(), "Summary of the video.");
+    }
+
+    #[test]
+    fn test_extract_openai_text_empty() {
+        let json = serde_json::json!({"choices": []});
+        assert!(extract_openai_text(&json).is_err());
+    }
+
+    #[test]
+    fn test_resolve_llm() {
+        assert_eq!(llm("claude-sonnet-4-6").provider, Provider::Anthropic);
+        assert_eq!(llm("gpt-4o").provider, Provider::OpenAi);
+        assert_eq!(llm("gpt-4o").base_url(), OPENAI_URL);
+
+        let local = llm("ollama:llama3.1:8b");
+        assert_eq!(local.provider, Provider::Ollama);
+        assert_eq!(local.model, "llama3.1:8b");
+        assert_eq!(local.base_url(), OLLAMA_URL);
+        assert_eq!(local.to_string(), "ollama:llama3.1:8b");
+
+        assert!(Llm::resolve("ollama:", &Config::default()).is_err());
+    }
+
+    #[test]
+    fn test_resolve_llm_from_config() {
+        let config = Config {
+            llm_provider: Some("openai".to_string()),
+            openai_base_url: Some("http://localhost:8000/v1/".to_string()),
+            ..Default::default()
+        };
+        let served = Llm::resolve("meta-llama/Llama-3.1-8B-Instruct", &config).unwrap();
+        assert_eq!(served.provider, Provider::OpenAi);
+        assert_eq!(served.base_url(), "http://localhost:8000/v1");
+
+        // Ollama's tag colon isn't a provider prefix
+        let config = Config {
+            llm_provider: Some("ollama".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(Llm::resolve("qwen2.5:14b", &config).unwrap().model, "qwen2.5:14b");
+
+        let config = Config {
+            llm_provider: Some("bard".to_string()),
+            ..Default::default()
+        };
+        assert!(Llm::resolve("gemini", &config).is_err());
+    }
+
+    #[test]
+    fn test_resolve_azure() {
+        assert!(
+            Llm::resolve("azure:gpt-4o-prod", &Config::default()).is_err_and(|e| e.to_string().contains("endpoint"))
+        );
+
+        let config = Config {
+            azure_openai_endpoint: Some("https://acme.openai.azure.com/".to_string()),
+            ..Default::default()
+        };
+        let azure = Llm::resolve("azure:gpt-4o-prod", &config).unwrap();
+        assert_eq!(azure.model, "gpt-4o-prod");
+        assert_eq!(azure.base_url(), "https://acme.openai.azure.com");
+    }
+
+    #[test]
+    fn test_ollama_event() {
+        let mut usage = TokenUsage::default();
+        let reply = serde_json::json!({
+            "model": "llama3.1",
+            "message": {"role": "assistant", "content": "A summary."},
+            "done": true,
+            "prompt_eval_count": 1500,
+            "eval_count": 80
+        });
+        assert_eq!(ollama_event(&reply, &mut usage).unwrap().as_deref(), Some("A summary."));
+        assert_eq!(usage.input_tokens, 1500);
+        assert_eq!(usage.output_tokens, 80);
+
+        let error = serde_json::json!({"error": "model 'llama9' not found"});
+        assert!(ollama_event(&error, &mut usage).is_err());
+    }
+
+    #[test]
+    fn test_gemini_reply() {
+        let gemini = Gemini {
+            base_url: GEMINI_URL.to_string(),
+        };
+        let reply = serde_json::json!({
+            "candidates": [{"content": {"role": "model", "parts": [
+                {"text": "Weighing options", "thought": true},
+                {"text": "The talk covers "},
+                {"text": "three ideas."}
+            ]}}],
+            "usageMetadata": {"promptTokenCount": 700, "candidatesTokenCount": 40, "thoughtsTokenCount": 25}
+        });
+        assert_eq!(gemini.text(&reply).unwrap(), "The talk covers three ideas.");
+        assert_eq!(
+            gemini.usage(&reply),
+            TokenUsage {
+                input_tokens: 700,
+                output_tokens: 65,
+                ..Default::default()
+            }
+        );
+
+        let blocked = serde_json::json!({"promptFeedback": {"blockReason": "SAFETY"}});
+        assert_eq!(
+            gemini.text(&blocked).unwrap_err().to_string(),
+            "Gemini API blocked the prompt (SAFETY)"
+        );
+
+        let listing = serde_json::json!({"models": [
+            {"name": "models/gemini-2.5-flash", "supportedGenerationMethods": ["generateContent", "countTokens"]},
+            {"name": "models/text-embedding-004", "supportedGenerationMethods": ["embedContent"]}
+        ]});
+        assert_eq!(gemini.models(&listing), vec!["gemini-2.5-flash"]);
+    }
+
+    #[test]
+    fn test_anthropic_stream_events() {
+        let events = [
+            serde_json::json!({"type": "message_start", "message": {"usage": {
+                "input_tokens": 12, "cache_read_input_tokens": 800, "output_tokens": 1
+            }}}),
+            serde_json::json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+            serde_json::json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Key "}}),
+            serde_json::json!({"type": "ping"}),
+            serde_json::json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "points"}}),
+            serde_json::json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}, "usage": {"output_tokens": 57}}),
+            serde_json::json!({"type": "message_stop"}),
+        ];
+        let mut usage = TokenUsage::default();
+        let text: String = events
+            .iter()
+            .filter_map(|e| anthropic_stream_event(e, &mut usage).unwrap())
+            .collect();
+        assert_eq!(text, "Key points");
+        assert_eq!(
+            usage,
+            TokenUsage {
+                input_tokens: 12,
+                output_tokens: 57,
+                cache_read_tokens: 800,
+                ..Default::default()
+            }
+        );
+
+        let error =
+            serde_json::json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}});
+        assert!(anthropic_stream_event(&error, &mut usage).is_err());
+    }
+
+    #[test]
+    fn test_openai_stream_events() {
+        let events = [
+            serde_json::json!({"choices": [{"index": 0, "delta": {"role": "assistant", "content": ""}}]}),
+            serde_json::json!({"choices": [{"index": 0, "delta": {"content": "Hello"}}]}),
+            serde_json::json!({"choices": [{"index": 0, "delta": {}, "finish_reason": "stop"}]}),
+            serde_json::json!({"choices": [], "usage": {"prompt_tokens": 90, "completion_tokens": 12}}),
+        ];
+        let mut usage = TokenUsage::default();
+        let text: String = events
+            .iter()
+            .filter_map(|e| openai_stream_event(e, &mut usage))
+            .collect();
+        assert_eq!(text, "Hello");
+        assert_eq!(usage.input_tokens, 90);
+        assert_eq!(usage.output_tokens, 12);
+    }
+
+    #[test]
+    fn test_extract_usage() {
+        let anthropic = serde_json::json!({"usage": {
+            "input_tokens": 20, "cache_creation_input_tokens": 1180, "cache_read_input_tokens": 0, "output_tokens": 340
+        }});
+        let usage = extract_anthropic_usage(&anthropic);
+        assert_eq!(
+            usage,
+            TokenUsage {
+                input_tokens: 20,
+                output_tokens: 340,
+                cache_write_tokens: 1180,
+                cache_read_tokens: 0
+            }
+        );
+        assert_eq!(usage.prompt_tokens(), 1200);
+
+        let openai =
+            serde_json::json!({"usage": {"prompt_tokens": 900, "completion_tokens": 120, "total_tokens": 1020}});
+        assert_eq!(
+            extract_openai_usage(&openai),
+            TokenUsage {
+                input_tokens: 900,
+                output_tokens: 120,
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(extract_openai_usage(&serde_json::json!({})), TokenUsage::default());
+    }
+}
@@ -1,14 +1,22 @@
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, IsTerminal, Write};
 use std::path::PathBuf;
 use std::process::Command;
-use std::time::Duration;
 
 use eyre::{Result, bail};
 use log::{debug, info};
 
 mod cli;
 
-use cli::{Cli, OutputFormat};
+use cli::{AskArgs, Cli, Command as Subcommand, ModelsArgs, OutputFormat, SummaryFormat, UsageArgs, UsageFormat};
+use ytx::cost::{Budget, format_usd};
+use ytx::llm::{Llm, Provider, TokenUsage};
+use ytx::policy::{Facts, Policy, TierSource};
+use ytx::prompt::Template;
+use ytx::summarize::Summary;
+use ytx::usage::{UsageKind, UsageRecord};
+use ytx::whisper::{VideoMetadata, WhisperOptions};
+use ytx::youtube::Player;
+use ytx::{PaidFailure, Transcript};
 
 fn setup_logging() -> Result<()> {
     let log_dir = log_dir();
@@ -32,9 +40,9 @@ fn log_dir() -> PathBuf {
         .join("logs")
 }
 
-fn tool_version(name: &str) -> Option<String> {
+fn tool_version(name: &str, flag: &str) -> Option<String> {
     Command::new(name)
-        .arg("--version")
+        .arg(flag)
         .output()
         .ok()
         .filter(|o| o.status.success())
@@ -49,44 +57,27 @@ fn tool_version(name: &str) -> Option<String> {
 }
 
 fn build_after_help() -> String {
-    let yt_dlp = tool_version("yt-dlp");
+    let yt_dlp = tool_version("yt-dlp", "--version");
+    let ffmpeg = tool_version("ffmpeg", "-version");
 
     let yt_dlp_line = match &yt_dlp {
         Some(v) => format!("  \x1b[32m✅\x1b[0m yt-dlp     {v}"),
-        None => "  \x1b[31m❌\x1b[0m yt-dlp     (not found — needed for --whisper-only)".to_string(),
+        None => "  \x1b[31m❌\x1b[0m yt-dlp     (not found — needed for Whisper transcription)".to_string(),
+    };
+
+    let ffmpeg_line = match &ffmpeg {
+        Some(v) => format!("  \x1b[32m✅\x1b[0m ffmpeg     {v}"),
+        None => "  \x1b[33m➖\x1b[0m ffmpeg     (not found — optional, places chunk cuts at silences; needed for --trim-silence and --speed)".to_string(),
     };
 
     let log_path = log_dir().join("ytx.log");
 
     format!(
-        "\nOPTIONAL TOOLS (only needed for --whisper-only):\n{yt_dlp_line}\n\nLogs are written to: {}",
+        "\nOPTIONAL TOOLS (only needed for Whisper transcription):\n{yt_dlp_line}\n{ffmpeg_line}\n\nLogs are written to: {}",
         log_path.display()
     )
 }
 
-/// Retry an async operation with exponential backoff
-async fn retry<F, Fut, T>(max_attempts: u32, operation: F) -> Result<T>
-where
-    F: Fn() -> Fut,
-    Fut: std::future::Future<Output = Result<T>>,
-{
-    let mut last_err = None;
-    for attempt in 0..max_attempts {
-        match operation().await {
-            Ok(val) => return Ok(val),
-            Err(e) => {
-                if attempt + 1 < max_attempts {
-                    let delay = Duration::from_millis(500 * 2u64.pow(attempt));
-                    debug!("Attempt {} failed: {e}, retrying in {delay:?}", attempt + 1);
-                    tokio::time::sleep(delay).await;
-                }
-                last_err = Some(e);
-            }
-        }
-    }
-    Err(last_err.unwrap())
-}
-
 #[tokio::main]
 async fn main() -> Result<()> {
     setup_logging()?;
@@ -99,8 +90,50 @@ async fn main() -> Result<()> {
     // Load config file (non-fatal if missing/invalid)
     let config = ytx::config::Config::load().unwrap_or_default();
 
-    // Apply config defaults (CLI flags take priority)
-    let lang = cli.lang.clone();
+    match cli.command {
+        Some(Subcommand::Usage(ref args)) => return usage_report(args),
+        Some(Subcommand::Models(ref args)) => return list_models(args, &config).await,
+        Some(Subcommand::Ask(_)) | None => {}
+    }
+
+    // Runs on a worker thread, so it still fires while the main task is
+    // blocked waiting on yt-dlp or ffmpeg
+    tokio::spawn(async {
+        let code = shutdown_signal().await;
+        ytx::workspace::cleanup_all();
+        std::process::exit(code);
+    });
+
+    match cli.command {
+        Some(Subcommand::Ask(ref args)) => ask(&cli, args, &config).await,
+        _ => run(&cli, &config).await,
+    }
+}
+
+/// Wait for SIGINT or SIGTERM, returning the conventional exit code
+async fn shutdown_signal() -> i32 {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => 130,
+                _ = term.recv() => 143,
+            },
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+                130
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        130
+    }
+}
+
+async fn run(cli: &Cli, config: &ytx::config::Config) -> Result<()> {
     let model = cli.model.clone();
 
     if cli.verbose {
@@ -116,7 +149,44 @@ async fn main() -> Result<()> {
         }
     }
 
-    let client = reqwest::Client::new();
+    // Naming a prompt asks for a summary
+    let template = match (&cli.prompt, &cli.prompt_file) {
+        (_, Some(path)) => Some(Template::from_file(path)?),
+        (Some(name), None) => Some(Template::load(name)?),
+        (None, None) if cli.summarize || cli.cite => Some(Template::default()),
+        (None, None) => None,
+    };
+    if cli.verbose
+        && let Some(ref template) = template
+    {
+        eprintln!("Prompt: {}", template.name);
+    }
+    let llm = match template {
+        Some(_) => Some(Llm::resolve(&model, config)?),
+        None => None,
+    };
+    if cli.verbose
+        && let Some(ref llm) = llm
+    {
+        eprintln!("LLM: {llm} at {}", llm.base_url());
+    }
+
+    // A streamed summary is printed before it can be reformatted
+    if cli.stream && cli.summary_format != SummaryFormat::Text {
+        bail!("--stream only prints text summaries; drop it to use --summary-format");
+    }
+    if cli.stream && cli.cite {
+        bail!("--stream prints citations before they are checked; drop it to use --cite");
+    }
+    if cli.stream && cli.format.holds_summary() && template.is_some() {
+        bail!("--stream prints the summary on its own, so it can't go in --format json, markdown or html output");
+    }
+    if cli.summary_format == SummaryFormat::Json
+        && matches!(cli.format, OutputFormat::Markdown | OutputFormat::Html)
+        && template.is_some()
+    {
+        bail!("markdown and html output hold a text summary; use --format json for a json summary");
+    }
 
     // Collect URLs: from arg or stdin
     let urls = if let Some(ref url) = cli.url {
@@ -130,6 +200,11 @@ async fn main() -> Result<()> {
         bail!("no URL or video ID provided\n\nUsage: ytx <URL>\n       echo <URL> | ytx");
     }
 
+    // Only ask before overspending if stdin is a person, not the URL list
+    let interactive = cli.url.is_some() && io::stdin().is_terminal();
+    let mut fetcher = Fetcher::new(cli, config, interactive)?;
+    let policy = fetcher.policy.clone();
+
     for url_input in &urls {
         let url_input = url_input.trim().to_string();
         if url_input.is_empty() {
@@ -139,69 +214,52 @@ async fn main() -> Result<()> {
         let video_id = ytx::extract_video_id(&url_input)
             .ok_or_else(|| eyre::eyre!("could not extract video ID from: {url_input}\n\nSupported formats:\n  https://www.youtube.com/watch?v=ID\n  https://youtu.be/ID\n  https://www.youtube.com/embed/ID\n  https://www.youtube.com/shorts/ID\n  <11-character video ID>"))?;
 
-        let whisper_model = ytx::whisper::WhisperModel::default();
-        let lang = lang.clone();
-
-        let transcript = if cli.whisper_only {
-            retry(3, || {
-                let client = &client;
-                let video_id = &video_id;
-                let lang = &lang;
-                let model = &whisper_model;
-                async move { ytx::whisper::transcribe(client, video_id, lang, model).await }
-            })
-            .await?
-        } else if let Some(cached) = (!cli.no_cache).then(|| ytx::cache::load(&video_id, &lang)).flatten() {
-            if cli.verbose {
-                eprintln!("Loaded transcript from cache");
-            }
-            cached
-        } else {
-            let caption_result = retry(3, || {
-                let client = &client;
-                let video_id = &video_id;
-                let lang = &lang;
-                async move { ytx::youtube::fetch_captions(client, video_id, lang).await }
-            })
-            .await;
-
-            let t = match caption_result {
-                Ok(t) => t,
-                Err(caption_err) => {
-                    debug!("Caption extraction failed: {caption_err}");
-                    eprintln!("[whisper fallback]");
-                    retry(3, || {
-                        let client = &client;
-                        let video_id = &video_id;
-                        let lang = &lang;
-                        let model = &whisper_model;
-                        async move { ytx::whisper::transcribe(client, video_id, lang, model).await }
-                    })
-                    .await?
-                }
-            };
-
-            if let Err(e) = ytx::cache::save(&t) {
-                debug!("Failed to cache transcript: {e}");
-            }
-            t
-        };
+        let (mut transcript, tier) = fetcher.fetch(&video_id).await?;
+        let tier_source = policy.tiers()[tier].source;
 
         if cli.verbose {
             eprintln!(
-                "Video: {} ({})\nSource: {}\nLanguage: {}\nSegments: {}",
+                "Video: {} ({})\nSource: {}\nLanguage: {}{}\nSegments: {}",
                 transcript.title,
                 transcript.video_id,
                 transcript.source,
                 transcript.language,
+                transcript
+                    .source_language
+                    .as_ref()
+                    .map(|l| format!(" (translated from {l})"))
+                    .unwrap_or_default(),
                 transcript.segments.len(),
             );
+            eprintln!("Tier: {} of {} ({tier_source})", tier + 1, policy.tiers().len());
+            if let Some(quality) = transcript.quality {
+                eprintln!(
+                    "Quality: {:.2} ({} dropped, {} flagged, {} loops collapsed)",
+                    quality.score, quality.dropped, quality.flagged, quality.collapsed
+                );
+            }
+        }
+
+        if let Some(ref names) = cli.speakers {
+            transcript.speakers.extend(names.clone());
+        }
+
+        // Formats that hold the summary need it first; others print it
+        // after the transcript
+        let mut summary = None;
+        if cli.format.holds_summary()
+            && let Some((template, llm)) = template.as_ref().zip(llm.as_ref())
+        {
+            summary = Some(summarize_video(&fetcher, config, &transcript, template, llm).await?);
         }
 
         let rendered = match cli.format {
             OutputFormat::Text => ytx::output::render_text(&transcript),
-            OutputFormat::Json => ytx::output::render_json(&transcript),
+            OutputFormat::Json => ytx::output::render_json_report(&transcript, tier_source, summary.as_ref()),
             OutputFormat::Srt => ytx::output::render_srt(&transcript),
+            OutputFormat::Vtt => ytx::output::render_vtt(&transcript),
+            OutputFormat::Markdown => ytx::output::render_markdown(&transcript, summary.as_ref()),
+            OutputFormat::Html => ytx::output::render_html(&transcript, summary.as_ref()),
         };
 
         if let Some(ref path) = cli.output {
@@ -213,11 +271,650 @@ async fn main() -> Result<()> {
             println!("{rendered}");
         }
 
-        if cli.summarize {
-            let summary = ytx::summarize::summarize(&client, &transcript, &model).await?;
-            println!("\n--- Summary ---\n{summary}");
+        if !cli.format.holds_summary()
+            && let Some((template, llm)) = template.as_ref().zip(llm.as_ref())
+        {
+            summary = Some(summarize_video(&fetcher, config, &transcript, template, llm).await?);
+        }
+
+        if let Some(summary) = summary {
+            let rendered = match (&summary.structured, cli.summary_format) {
+                (Some(structured), _) => ytx::output::render_summary_json(structured),
+                (None, SummaryFormat::Markdown) => ytx::output::render_summary_markdown(&summary.text, &video_id),
+                (None, SummaryFormat::Html) => ytx::output::render_summary_html(&summary.text, &video_id),
+                (None, SummaryFormat::Text | SummaryFormat::Json) => summary.text,
+            };
+            if let Some(ref path) = cli.summary_output {
+                std::fs::write(path, &rendered)?;
+                if cli.verbose {
+                    eprintln!("Summary written to: {}", path.display());
+                }
+            } else if !cli.format.holds_summary() && !cli.stream {
+                println!("\n--- Summary ---\n{rendered}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Summarize a transcript, or reuse the cached summary of the same
+/// transcript by the same model and prompt, then check its citations and
+/// link its quotes. A streamed summary is printed as it arrives, and a
+/// cached one printed whole in its place.
+async fn summarize_video(
+    fetcher: &Fetcher<'_>,
+    config: &ytx::config::Config,
+    transcript: &Transcript,
+    template: &Template,
+    llm: &Llm,
+) -> Result<Summary> {
+    let cli = fetcher.cli;
+    let video_id = &transcript.video_id;
+    let chunk_tokens = ytx::summarize::chunk_tokens(llm, &config.summary_chunk_tokens);
+    let split = ytx::summarize::needs_split(transcript, chunk_tokens);
+    // Chapters (to split long transcripts at) and the channel name only
+    // come from yt-dlp
+    let metadata = if split || template.uses("channel") {
+        ytx::whisper::fetch_metadata(video_id).unwrap_or_else(|e| {
+            debug!("Failed to fetch video metadata: {e}");
+            VideoMetadata::default()
+        })
+    } else {
+        VideoMetadata::default()
+    };
+    let chapters = metadata.chapters.filter(|_| split).unwrap_or_default();
+    let opts = ytx::summarize::SummaryOptions {
+        llm,
+        template,
+        max_tokens: chunk_tokens,
+        chapters: &chapters,
+        channel: metadata.channel.as_deref(),
+        stream: cli.stream,
+        cite: cli.cite,
+        structured: cli.summary_format == SummaryFormat::Json,
+    };
+
+    let key = ytx::summarize::cache_key(transcript, &opts);
+    let cached = if cli.no_cache {
+        None
+    } else {
+        ytx::cache::load_summary(video_id, &key)
+    };
+    let mut summary = match cached {
+        Some(summary) => {
+            if cli.verbose {
+                eprintln!("Summary: from cache");
+            }
+            if cli.stream {
+                println!("\n--- Summary ---\n{}", summary.text);
+            }
+            summary
+        }
+        None => {
+            if cli.stream {
+                println!("\n--- Summary ---");
+            }
+            let summary = match ytx::summarize::summarize(&fetcher.client, transcript, &opts).await {
+                Ok(summary) => summary,
+                Err(e) => {
+                    // Requests that went through before the failure were still paid for
+                    if let Some(usage) = PaidFailure::<TokenUsage>::spent(&e) {
+                        record_summary_usage(fetcher, llm, video_id, usage);
+                    }
+                    return Err(e);
+                }
+            };
+            if cli.stream {
+                println!();
+            }
+            if cli.verbose && summary.parts > 1 {
+                eprintln!("Summarized in {} parts ({} chapters)", summary.parts, chapters.len());
+            }
+            record_summary_usage(fetcher, llm, video_id, &summary.usage);
+            if let Err(e) = ytx::cache::save_summary(video_id, &key, &summary) {
+                debug!("Failed to cache summary: {e}");
+            }
+            summary
+        }
+    };
+
+    if let Some(structured) = &mut summary.structured {
+        structured.link(video_id);
+    } else if cli.cite {
+        let cited = ytx::cite::check(&summary.text, transcript);
+        if !cited.dropped.is_empty() {
+            eprintln!(
+                "Dropped {} citation(s) to times not in the transcript: {}",
+                cited.dropped.len(),
+                cited.dropped.join(", ")
+            );
+        }
+        summary.text = cited.text;
+    }
+    Ok(summary)
+}
+
+/// What fetching every video's transcript shares, set up once per run
+struct Fetcher<'a> {
+    cli: &'a Cli,
+    client: reqwest::Client,
+    policy: Policy,
+    whisper_model: ytx::whisper::WhisperModel,
+    temp_root: PathBuf,
+    glossary: Vec<String>,
+    budget: Budget,
+    project: Option<String>,
+    /// Whether a person is there to confirm spending
+    interactive: bool,
+}
+
+impl<'a> Fetcher<'a> {
+    fn new(cli: &'a Cli, config: &ytx::config::Config, interactive: bool) -> Result<Self> {
+        let whisper_model = match (&cli.whisper_model, &config.whisper_model) {
+            (Some(m), _) => m.clone(),
+            (None, Some(name)) => name.parse()?,
+            (None, None) => ytx::whisper::WhisperModel::default(),
+        };
+
+        let policy = if cli.whisper_only {
+            Policy::whisper_only()
+        } else {
+            let policy = Policy::from_config(&config.tiers)?;
+            if cli.no_cache {
+                policy.without(TierSource::Cache)?
+            } else {
+                policy
+            }
+        };
+        debug!("Fetch policy: {policy:?}");
+
+        Ok(Self {
+            cli,
+            client: reqwest::Client::new(),
+            policy,
+            whisper_model,
+            temp_root: cli
+                .temp_dir
+                .clone()
+                .or_else(|| config.temp_dir.clone())
+                .unwrap_or_else(ytx::workspace::default_root),
+            glossary: ytx::config::load_glossary(),
+            budget: Budget::new(cli.max_cost.or(config.max_cost_per_run)),
+            project: cli.project.clone().or_else(|| config.project.clone()),
+            interactive,
+        })
+    }
+
+    /// Fetch a video's transcript by the policy, caching it if it's new,
+    /// and return it with the index of the tier that produced it
+    async fn fetch(&mut self, video_id: &str) -> Result<(Transcript, usize)> {
+        let cli = self.cli;
+
+        // A translation is always English, so English captions serve as well.
+        // Translating from English is a no-op, so `en` there means "not given"
+        // and the spoken language is detected.
+        let (lang, spoken_lang) = if cli.translate {
+            let spoken = if cli.lang == "en" {
+                ytx::AUTO_LANG
+            } else {
+                cli.lang.as_str()
+            };
+            ("en".to_string(), spoken.to_string())
+        } else {
+            (cli.lang.clone(), cli.lang.clone())
+        };
+        let whisper_opts = WhisperOptions {
+            model: self.whisper_model.clone(),
+            lang: spoken_lang,
+            translate: cli.translate,
+            jobs: usize::from(cli.whisper_jobs),
+            temp_root: self.temp_root.clone(),
+            keep_audio: cli.keep_audio,
+            glossary: self.glossary.clone(),
+            preprocess: ytx::whisper::Preprocess {
+                trim_silence: cli.trim_silence,
+                speed: cli.speed,
+            },
+        };
+
+        let request = Request {
+            client: &self.client,
+            video_id,
+            lang: &lang,
+            translate: cli.translate,
+            whisper: &whisper_opts,
+            interactive: self.interactive,
+            project: self.project.as_deref(),
+        };
+        let (transcript, tier) = fetch_transcript(&request, &self.policy, &mut self.budget).await?;
+
+        if self.policy.tiers()[tier].source != TierSource::Cache
+            && let Err(e) = ytx::cache::save(&transcript)
+        {
+            debug!("Failed to cache transcript: {e}");
+        }
+
+        if cli.verbose
+            && cli.keep_audio
+            && matches!(
+                transcript.source,
+                ytx::TranscriptSource::Whisper | ytx::TranscriptSource::Local
+            )
+            && let Some(kept) = ytx::whisper::kept_audio_path(video_id)
+        {
+            eprintln!("Audio kept at: {}", kept.display());
+        }
+
+        Ok((transcript, tier))
+    }
+}
+
+/// One video's transcript request, as the policy tiers see it
+struct Request<'a> {
+    client: &'a reqwest::Client,
+    video_id: &'a str,
+    lang: &'a str,
+    translate: bool,
+    whisper: &'a WhisperOptions,
+    interactive: bool,
+    project: Option<&'a str>,
+}
+
+/// Details about a video gathered while walking the tiers, each looked up
+/// at most once and only when a tier needs it
+#[derive(Default)]
+struct Probe {
+    player: Option<Result<Player, String>>,
+    metadata: Option<VideoMetadata>,
+}
+
+impl Probe {
+    async fn player(&mut self, req: &Request<'_>) -> Result<&Player> {
+        let player = match self.player.take() {
+            Some(player) => player,
+            None => ytx::retry(3, || ytx::youtube::fetch_player(req.client, req.video_id, req.lang))
+                .await
+                .map_err(|e| e.to_string()),
+        };
+        self.player.insert(player).as_ref().map_err(|e| eyre::eyre!("{e}"))
+    }
+
+    fn metadata(&mut self, video_id: &str) -> &VideoMetadata {
+        self.metadata.get_or_insert_with(|| {
+            ytx::whisper::fetch_metadata(video_id).unwrap_or_else(|e| {
+                debug!("Failed to fetch video metadata: {e}");
+                VideoMetadata::default()
+            })
+        })
+    }
+
+    /// Length in seconds, from YouTube itself if possible since that's
+    /// quicker than asking yt-dlp
+    async fn duration(&mut self, req: &Request<'_>) -> Option<f64> {
+        if let Some(secs) = self.metadata.as_ref().and_then(|m| m.duration) {
+            return Some(secs);
         }
+        if let Ok(player) = self.player(req).await
+            && let Some(secs) = player.duration
+        {
+            return Some(secs);
+        }
+        self.metadata(req.video_id).duration
     }
+}
+
+/// Try the policy's tiers in order until one produces a transcript,
+/// returning it with the index of the tier that did
+async fn fetch_transcript(req: &Request<'_>, policy: &Policy, budget: &mut Budget) -> Result<(Transcript, usize)> {
+    let mut probe = Probe::default();
+    let mut failures = Vec::new();
+    let mut fell_back = false;
+
+    for (i, tier) in policy.tiers().iter().enumerate() {
+        let opts = match tier.whisper_model()? {
+            Some(model) => WhisperOptions {
+                model,
+                ..req.whisper.clone()
+            },
+            None => req.whisper.clone(),
+        };
 
+        let mut facts = Facts::default();
+        if tier.needs_caption_tracks() {
+            facts.manual_captions = probe.player(req).await.ok().map(Player::has_manual_captions);
+        }
+        if tier.needs_duration() {
+            facts.duration_secs = probe.duration(req).await;
+            facts.estimated_cost = facts
+                .duration_secs
+                .map(|secs| ytx::cost::estimate_whisper(&opts.model, opts.preprocess.billed_secs(secs)));
+        }
+        if let Some(reason) = tier.skip_reason(&facts) {
+            debug!("Skipping tier {} ({}): {reason}", i + 1, tier.source);
+            failures.push(format!("{}: skipped ({reason})", tier.source));
+            fell_back = true;
+            continue;
+        }
+
+        if fell_back {
+            eprintln!("[{} fallback]", tier.source);
+        }
+
+        let result = match tier.source {
+            TierSource::Cache => ytx::cache::load(req.video_id, req.lang).ok_or_else(|| eyre::eyre!("not cached")),
+            TierSource::Captions => match probe.player(req).await {
+                Ok(player) => caption_transcribe(req, player, tier.manual_only).await,
+                Err(e) => Err(e),
+            },
+            TierSource::AltClient => {
+                let client = tier.inner_tube_client();
+                match ytx::retry(3, || {
+                    ytx::youtube::fetch_player_as(req.client, req.video_id, req.lang, client)
+                })
+                .await
+                {
+                    Ok(player) => caption_transcribe(req, &player, tier.manual_only)
+                        .await
+                        .map_err(|e| eyre::eyre!("as {client}: {e}")),
+                    Err(e) => Err(e),
+                }
+            }
+            TierSource::Local => {
+                let metadata = probe.metadata(req.video_id).clone();
+                // Checked by Policy::new
+                let model_path = tier.model_path.as_deref().expect("local tier has a model_path");
+                ytx::whisper::transcribe_local(req.video_id, &metadata, model_path, tier.local_command(), &opts).await
+            }
+            TierSource::Whisper => {
+                let metadata = probe.metadata(req.video_id).clone();
+                whisper_transcribe(req, &opts, &metadata, budget).await
+            }
+        };
+
+        match result {
+            Ok(transcript) => return Ok((transcript, i)),
+            Err(e) => {
+                debug!("Tier {} ({}) failed: {e}", i + 1, tier.source);
+                failures.push(format!("{}: {e}", tier.source));
+                // A cache miss is routine, not a fallback
+                fell_back |= tier.source != TierSource::Cache;
+            }
+        }
+    }
+
+    bail!(
+        "no tier produced a transcript for {}:\n  {}",
+        req.video_id,
+        failures.join("\n  ")
+    )
+}
+
+/// Fetch the caption track from a video lookup, for the captions and
+/// alt_client tiers
+async fn caption_transcribe(req: &Request<'_>, player: &Player, manual_only: bool) -> Result<Transcript> {
+    let transcript = ytx::retry(3, || {
+        ytx::youtube::fetch_track(req.client, req.video_id, player, req.lang, manual_only)
+    })
+    .await?;
+
+    // Captions fall back to another language if there's no English track,
+    // which is no use when an English translation was asked for
+    if req.translate && transcript.language != "en" && !transcript.language.starts_with("en-") {
+        bail!("no English captions (found {})", transcript.language);
+    }
+    Ok(transcript)
+}
+
+/// Record the tokens a summary used in the usage ledger
+fn record_summary_usage(fetcher: &Fetcher<'_>, llm: &Llm, video_id: &str, usage: &TokenUsage) {
+    // Local models cost nothing, even if one shares a paid model's name
+    let cost = if llm.is_local() {
+        0.0
+    } else {
+        ytx::cost::estimate_llm(&llm.model, usage.input_tokens, usage.output_tokens)
+    };
+    record_usage(UsageRecord {
+        input_tokens: usage.input_tokens,
+        output_tokens: usage.output_tokens,
+        cost,
+        ..UsageRecord::new(UsageKind::Llm, &llm.model, video_id, fetcher.project.as_deref())
+    });
+}
+
+/// Run the paid Whisper tier after printing its estimated cost and checking
+/// it against the run's spending cap, asking first when interactive
+async fn whisper_transcribe(
+    req: &Request<'_>,
+    opts: &WhisperOptions,
+    metadata: &VideoMetadata,
+    budget: &mut Budget,
+) -> Result<Transcript> {
+    let estimate = match metadata.duration {
+        Some(secs) => {
+            // Trimmed silence isn't known until the audio is analysed, so
+            // this is an upper bound when trimming
+            let billed = opts.preprocess.billed_secs(secs);
+            let cost = ytx::cost::estimate_whisper(&opts.model, billed);
+            let sped_up = if billed < secs {
+                format!(" ({:.1} min at {}x)", billed / 60.0, opts.preprocess.speed)
+            } else {
+                String::new()
+            };
+            eprintln!(
+                "[whisper] {:.1} min{sped_up} with {}, estimated cost {}",
+                secs / 60.0,
+                opts.model.api_name(),
+                format_usd(cost)
+            );
+            cost
+        }
+        None if budget.cap().is_some() => {
+            bail!("could not determine the video's duration, so the Whisper cost can't be checked against --max-cost")
+        }
+        None => {
+            eprintln!("[whisper] duration unknown, cost not estimated");
+            0.0
+        }
+    };
+
+    if !budget.allows(estimate) {
+        let message = format!(
+            "estimated Whisper cost {} exceeds the remaining budget of {} (cap {})",
+            format_usd(estimate),
+            format_usd(budget.remaining().unwrap_or_default()),
+            format_usd(budget.cap().unwrap_or_default()),
+        );
+        if !(req.interactive && confirm(&format!("{message}. Continue anyway?"))?) {
+            bail!("{message}\n\nRaise --max-cost (or max_cost_per_run in config) to allow it");
+        }
+    }
+
+    let (transcript, uploaded_secs) = match ytx::whisper::transcribe(req.client, req.video_id, metadata, opts).await {
+        Ok(transcribed) => transcribed,
+        Err(e) => {
+            // Chunks finished before the failure were still paid for
+            if let Some(&secs) = PaidFailure::<f64>::spent(&e) {
+                charge_whisper(req, opts, budget, secs);
+            }
+            return Err(e);
+        }
+    };
+
+    // Bill on the chunks this run uploaded, since a resumed job reuses the
+    // rest; otherwise on the known duration, or what was transcribed if it
+    // wasn't known
+    let secs = uploaded_secs.unwrap_or_else(|| {
+        opts.preprocess.billed_secs(metadata.duration.unwrap_or_else(|| {
+            transcript
+                .segments
+                .last()
+                .map(|s| s.start + s.duration)
+                .unwrap_or_default()
+        }))
+    });
+    charge_whisper(req, opts, budget, secs);
+    Ok(transcript)
+}
+
+/// Count `secs` of uploaded audio against the budget and in the usage ledger
+fn charge_whisper(req: &Request<'_>, opts: &WhisperOptions, budget: &mut Budget, secs: f64) {
+    if secs <= 0.0 {
+        // Every chunk came from an earlier run, which already paid for it
+        return;
+    }
+    let cost = ytx::cost::estimate_whisper(&opts.model, secs);
+    budget.record(cost);
+    record_usage(UsageRecord {
+        audio_minutes: secs / 60.0,
+        cost,
+        ..UsageRecord::new(
+            UsageKind::Transcription,
+            opts.model.api_name(),
+            req.video_id,
+            req.project,
+        )
+    });
+}
+
+/// Append to the usage ledger; a failure here must not fail the run
+fn record_usage(entry: UsageRecord) {
+    if let Err(e) = ytx::usage::record(&entry) {
+        debug!("Failed to record usage: {e}");
+    }
+}
+
+/// Print totals from the usage ledger
+fn usage_report(args: &UsageArgs) -> Result<()> {
+    let mut records = ytx::usage::load()?;
+    records.retain(|r| {
+        args.since
+            .as_ref()
+            .is_none_or(|since| r.day().as_str() >= since.as_str())
+            && args.user.as_ref().is_none_or(|user| &r.user == user)
+            && args
+                .project
+                .as_ref()
+                .is_none_or(|project| r.project.as_ref() == Some(project))
+    });
+
+    let rows = ytx::usage::aggregate(&records, args.by);
+    let rendered = match args.format {
+        UsageFormat::Table if rows.is_empty() => {
+            format!("No usage recorded ({})", ytx::usage::ledger_path().display())
+        }
+        UsageFormat::Table => ytx::usage::render_table(&rows, args.by),
+        UsageFormat::Json => ytx::usage::render_json(&rows),
+        UsageFormat::Csv => ytx::usage::render_csv(&rows, args.by),
+    };
+    println!("{rendered}");
+    Ok(())
+}
+
+/// Print the models a provider offers, one per line
+async fn list_models(args: &ModelsArgs, config: &ytx::config::Config) -> Result<()> {
+    let provider: Provider = match args.provider.as_ref().or(config.llm_provider.as_ref()) {
+        Some(name) => name.parse()?,
+        None => Provider::Anthropic,
+    };
+    let api = provider.api(config)?;
+    for model in ytx::llm::list_models(&reqwest::Client::new(), api.as_ref()).await? {
+        println!("{model}");
+    }
+    Ok(())
+}
+
+/// Answer questions about one video from its transcript: the one given,
+/// or each line of stdin in turn, with the earlier ones as context
+async fn ask(cli: &Cli, args: &AskArgs, config: &ytx::config::Config) -> Result<()> {
+    let Some(video_id) = ytx::extract_video_id(&args.url) else {
+        bail!("could not extract video ID from: {}", args.url);
+    };
+    let llm = Llm::resolve(&cli.model, config)?;
+    let interactive = io::stdin().is_terminal();
+    let mut fetcher = Fetcher::new(cli, config, interactive)?;
+    let (transcript, _) = fetcher.fetch(&video_id).await?;
+
+    // Parts of a transcript can't be asked about in turn, so it must fit whole
+    let max_tokens = ytx::summarize::chunk_tokens(&llm, &config.summary_chunk_tokens);
+    if ytx::summarize::needs_split(&transcript, max_tokens) {
+        bail!(
+            "the transcript of '{}' is over the {max_tokens} tokens {llm} takes at once; \
+             try a model with a longer context or raise summary_chunk_tokens",
+            transcript.title
+        );
+    }
+    if cli.verbose {
+        eprintln!("LLM: {llm} at {}", llm.base_url());
+    }
+
+    let mut conversation = ytx::ask::Conversation::new(&transcript);
+    let client = fetcher.client.clone();
+    let mut answer = async |question: &str| -> Result<()> {
+        let (_, usage) = conversation.ask(&client, &llm, question).await?;
+        println!();
+        let cost = if llm.is_local() {
+            0.0
+        } else {
+            ytx::cost::estimate_llm_usage(&llm.model, &usage)
+        };
+        if cli.verbose {
+            eprintln!(
+                "Tokens: {} in ({} cached), {} out, {}",
+                usage.prompt_tokens(),
+                usage.cache_read_tokens,
+                usage.output_tokens,
+                format_usd(cost)
+            );
+        }
+        record_usage(UsageRecord {
+            input_tokens: usage.prompt_tokens(),
+            output_tokens: usage.output_tokens,
+            cost,
+            ..UsageRecord::new(UsageKind::Llm, &llm.model, &video_id, fetcher.project.as_deref())
+        });
+        Ok(())
+    };
+
+    if let Some(question) = &args.question {
+        return answer(question).await;
+    }
+
+    if interactive {
+        eprintln!("Asking about \"{}\"; an empty line or Ctrl-D ends", transcript.title);
+    }
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        if interactive {
+            eprint!("> ");
+            io::stderr().flush()?;
+        }
+        let Some(line) = lines.next().transpose()? else {
+            break;
+        };
+        let question = line.trim();
+        if question.is_empty() {
+            if interactive {
+                break;
+            }
+            continue;
+        }
+        // One failed answer needn't end the conversation
+        if let Err(e) = answer(question).await {
+            if !interactive {
+                return Err(e);
+            }
+            eprintln!("Error: {e}");
+        }
+    }
     Ok(())
 }
+
+/// Ask a yes/no question on stderr, defaulting to no
+fn confirm(question: &str) -> Result<bool> {
+    eprint!("{question} [y/N] ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
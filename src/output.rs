@@ -1,13 +1,26 @@
+use regex::Regex;
+
 use crate::Transcript;
+use crate::cite;
+use crate::policy::TierSource;
+use crate::structured::StructuredSummary;
+use crate::summarize::Summary;
 
-/// Render transcript as plain text (one segment per line, no timestamps)
+/// Render transcript as plain text (one segment per line, no timestamps).
+/// Diarized transcripts get a `NAME:` prefix whenever the speaker changes.
 pub fn render_text(transcript: &Transcript) -> String {
-    transcript
-        .segments
-        .iter()
-        .map(|s| s.text.as_str())
-        .collect::<Vec<_>>()
-        .join("\n")
+    let mut lines = Vec::with_capacity(transcript.segments.len());
+    let mut current_speaker = None;
+    for seg in &transcript.segments {
+        match &seg.speaker {
+            Some(label) if current_speaker != Some(label) => {
+                lines.push(format!("{}: {}", transcript.speaker_name(label), seg.text));
+                current_speaker = Some(label);
+            }
+            _ => lines.push(seg.text.clone()),
+        }
+    }
+    lines.join("\n")
 }
 
 /// Render transcript as JSON with timestamps
@@ -15,18 +28,210 @@ pub fn render_json(transcript: &Transcript) -> String {
     serde_json::to_string_pretty(transcript).unwrap_or_default()
 }
 
+/// Render transcript as JSON for the command line: with the fetch tier that
+/// produced it under `tier`, and its summary, if any, under `summary`
+pub fn render_json_report(transcript: &Transcript, tier: TierSource, summary: Option<&Summary>) -> String {
+    let mut json = serde_json::to_value(transcript).unwrap_or_default();
+    json["tier"] = serde_json::to_value(tier).unwrap_or_default();
+    if let Some(summary) = summary {
+        json["summary"] = serde_json::to_value(summary).unwrap_or_default();
+    }
+    serde_json::to_string_pretty(&json).unwrap_or_default()
+}
+
 /// Render transcript as SRT subtitle format
 pub fn render_srt(transcript: &Transcript) -> String {
     let mut output = String::new();
     for (i, seg) in transcript.segments.iter().enumerate() {
         let start = format_srt_time(seg.start);
         let end = format_srt_time(seg.start + seg.duration);
-        output.push_str(&format!("{}\n{start} --> {end}\n{}\n\n", i + 1, seg.text));
+        let text = match &seg.speaker {
+            Some(label) => format!("{}: {}", transcript.speaker_name(label), seg.text),
+            None => seg.text.clone(),
+        };
+        output.push_str(&format!("{}\n{start} --> {end}\n{text}\n\n", i + 1));
     }
     output.truncate(output.trim_end().len());
     output
 }
 
+/// Render transcript as WebVTT, using voice tags for speakers
+pub fn render_vtt(transcript: &Transcript) -> String {
+    let mut output = String::from("WEBVTT\n\n");
+    for seg in &transcript.segments {
+        let start = format_vtt_time(seg.start);
+        let end = format_vtt_time(seg.start + seg.duration);
+        let text = match &seg.speaker {
+            Some(label) => format!("<v {}>{}", transcript.speaker_name(label), seg.text),
+            None => seg.text.clone(),
+        };
+        output.push_str(&format!("{start} --> {end}\n{text}\n\n"));
+    }
+    output.truncate(output.trim_end().len());
+    output
+}
+
+/// Render transcript as a Markdown document: the summary, if given, then
+/// each segment linked to its moment in the video
+pub fn render_markdown(transcript: &Transcript, summary: Option<&Summary>) -> String {
+    let mut sections = vec![format!("# {}", transcript.title)];
+    if let Some(summary) = summary {
+        // Under "## Summary", so its own headings move down a level
+        let text = render_summary_markdown(&summary.text, &transcript.video_id);
+        let text: Vec<String> = text
+            .lines()
+            .map(|line| {
+                if line.starts_with('#') {
+                    format!("#{line}")
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect();
+        sections.push(format!("## Summary\n\n{}", text.join("\n")));
+    }
+
+    let mut lines = Vec::with_capacity(transcript.segments.len());
+    let mut current_speaker = None;
+    for seg in &transcript.segments {
+        let link = stamp_link(transcript, seg.start, |label, url| format!("[{label}]({url})"));
+        match &seg.speaker {
+            Some(label) if current_speaker != Some(label) => {
+                lines.push(format!("- {link} **{}:** {}", transcript.speaker_name(label), seg.text));
+                current_speaker = Some(label);
+            }
+            _ => lines.push(format!("- {link} {}", seg.text)),
+        }
+    }
+    sections.push(format!("## Transcript\n\n{}", lines.join("\n")));
+    sections.join("\n\n")
+}
+
+/// Render transcript as an HTML page: the summary, if given, then each
+/// segment linked to its moment in the video
+pub fn render_html(transcript: &Transcript, summary: Option<&Summary>) -> String {
+    let title = html_escape::encode_text(&transcript.title);
+    let mut html = vec![
+        "<!DOCTYPE html>".to_string(),
+        "<html>".to_string(),
+        "<head>".to_string(),
+        "<meta charset=\"utf-8\">".to_string(),
+        format!("<title>{title}</title>"),
+        "</head>".to_string(),
+        "<body>".to_string(),
+        format!("<h1>{title}</h1>"),
+    ];
+    if let Some(summary) = summary {
+        html.push("<section class=\"summary\">".to_string());
+        html.push("<h2>Summary</h2>".to_string());
+        html.push(render_summary_html(&summary.text, &transcript.video_id));
+        html.push("</section>".to_string());
+    }
+
+    html.push("<section class=\"transcript\">".to_string());
+    html.push("<h2>Transcript</h2>".to_string());
+    let mut current_speaker = None;
+    for seg in &transcript.segments {
+        let link = stamp_link(transcript, seg.start, |label, url| {
+            format!("<a href=\"{url}\">{label}</a>")
+        });
+        let text = html_escape::encode_text(&seg.text);
+        match &seg.speaker {
+            Some(label) if current_speaker != Some(label) => {
+                let name = transcript.speaker_name(label);
+                html.push(format!(
+                    "<p>{link} <strong>{}:</strong> {text}</p>",
+                    html_escape::encode_text(&name)
+                ));
+                current_speaker = Some(label);
+            }
+            _ => html.push(format!("<p>{link} {text}</p>")),
+        }
+    }
+    html.push("</section>".to_string());
+    html.push("</body>".to_string());
+    html.push("</html>".to_string());
+    html.join("\n")
+}
+
+/// A segment's `mm:ss` time, linked to that moment by `link(label, url)`
+fn stamp_link(transcript: &Transcript, start: f64, link: impl Fn(&str, &str) -> String) -> String {
+    let stamp = cite::stamp(start);
+    link(
+        stamp.trim_matches(['[', ']']),
+        &cite::video_url(&transcript.video_id, start as u64),
+    )
+}
+
+/// Render a structured summary as JSON
+pub fn render_summary_json(summary: &StructuredSummary) -> String {
+    serde_json::to_string_pretty(summary).unwrap_or_default()
+}
+
+/// Render a summary as Markdown, with its `[mm:ss]` citations linked to
+/// those moments in the video
+pub fn render_summary_markdown(text: &str, video_id: &str) -> String {
+    cite::replace(text, |times| {
+        times
+            .iter()
+            .map(|&(label, secs)| format!("[{label}]({})", cite::video_url(video_id, secs)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    })
+}
+
+/// Render a summary's Markdown as an HTML fragment: headings, bullet lists
+/// and paragraphs, with citations linked to those moments in the video
+pub fn render_summary_html(text: &str, video_id: &str) -> String {
+    let mut html = Vec::new();
+    let mut in_list = false;
+    for line in text.lines().map(str::trim) {
+        let item = line.strip_prefix("- ").or_else(|| line.strip_prefix("* "));
+        if item.is_none() && in_list {
+            html.push("</ul>".to_string());
+            in_list = false;
+        }
+        if let Some(item) = item {
+            if !in_list {
+                html.push("<ul>".to_string());
+                in_list = true;
+            }
+            html.push(format!("<li>{}</li>", inline_html(item, video_id)));
+        } else if let Some((hashes, heading)) = line.split_once(' ')
+            && !hashes.is_empty()
+            && hashes.len() <= 5
+            && hashes.chars().all(|c| c == '#')
+        {
+            let level = hashes.len() + 1;
+            html.push(format!("<h{level}>{}</h{level}>", inline_html(heading, video_id)));
+        } else if !line.is_empty() {
+            html.push(format!("<p>{}</p>", inline_html(line, video_id)));
+        }
+    }
+    if in_list {
+        html.push("</ul>".to_string());
+    }
+    html.join("\n")
+}
+
+/// Escape a line of summary text, keeping `**bold**` and linking citations
+fn inline_html(text: &str, video_id: &str) -> String {
+    let escaped = html_escape::encode_text(text);
+    let bold = Regex::new(r"\*\*(.+?)\*\*").expect("valid regex");
+    let text = bold.replace_all(&escaped, "<strong>$1</strong>");
+    cite::replace(&text, |times| {
+        times
+            .iter()
+            .map(|&(label, secs)| format!("<a href=\"{}\">{label}</a>", cite::video_url(video_id, secs)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    })
+}
+
+fn format_vtt_time(seconds: f64) -> String {
+    format_srt_time(seconds).replace(',', ".")
+}
+
 fn format_srt_time(seconds: f64) -> String {
     let total_ms = (seconds * 1000.0) as u64;
     let ms = total_ms % 1000;
@@ -48,22 +253,42 @@ mod tests {
             video_id: "test123".to_string(),
             title: "Test Video".to_string(),
             language: "en".to_string(),
+            source_language: None,
             source: TranscriptSource::Caption,
             segments: vec![
                 Segment {
                     text: "Hello world".to_string(),
                     start: 0.0,
                     duration: 1.5,
+                    ..Default::default()
                 },
                 Segment {
                     text: "This is a test".to_string(),
                     start: 1.5,
                     duration: 2.0,
+                    ..Default::default()
                 },
             ],
+            speakers: Default::default(),
+            quality: None,
         }
     }
 
+    fn diarized_transcript() -> Transcript {
+        let mut t = sample_transcript();
+        t.segments[0].speaker = Some("A".to_string());
+        t.segments[1].speaker = Some("B".to_string());
+        t.segments.push(Segment {
+            text: "Still me".to_string(),
+            start: 3.5,
+            duration: 1.0,
+            speaker: Some("B".to_string()),
+            ..Default::default()
+        });
+        t.speakers.insert("A".to_string(), "Alice".to_string());
+        t
+    }
+
     #[test]
     fn test_render_text() {
         let t = sample_transcript();
@@ -77,8 +302,11 @@ mod tests {
             video_id: "empty".to_string(),
             title: "Empty".to_string(),
             language: "en".to_string(),
+            source_language: None,
             source: TranscriptSource::Caption,
             segments: vec![],
+            speakers: Default::default(),
+            quality: None,
         };
         assert_eq!(render_text(&t), "");
     }
@@ -110,6 +338,121 @@ This is a test";
         assert_eq!(output, expected);
     }
 
+    #[test]
+    fn test_render_text_with_speakers() {
+        let t = diarized_transcript();
+        assert_eq!(
+            render_text(&t),
+            "Alice: Hello world\nSPEAKER B: This is a test\nStill me"
+        );
+    }
+
+    #[test]
+    fn test_render_srt_with_speakers() {
+        let t = diarized_transcript();
+        let output = render_srt(&t);
+        assert!(output.contains("00:00:00,000 --> 00:00:01,500\nAlice: Hello world\n"));
+        assert!(output.ends_with("00:00:03,500 --> 00:00:04,500\nSPEAKER B: Still me"));
+    }
+
+    #[test]
+    fn test_render_json_report() {
+        let summary = Summary {
+            text: "- Ownership [00:01]".to_string(),
+            model: "claude-sonnet-4-6".to_string(),
+            usage: Default::default(),
+            parts: 1,
+            structured: None,
+        };
+        let parsed: serde_json::Value = serde_json::from_str(&render_json_report(
+            &sample_transcript(),
+            TierSource::AltClient,
+            Some(&summary),
+        ))
+        .unwrap();
+        assert_eq!(parsed["video_id"], "test123");
+        assert_eq!(parsed["tier"], "alt_client");
+        assert_eq!(parsed["summary"]["text"], "- Ownership [00:01]");
+        assert_eq!(parsed["summary"]["model"], "claude-sonnet-4-6");
+        assert!(parsed["summary"].get("structured").is_none());
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(&render_json_report(&sample_transcript(), TierSource::Local, None)).unwrap();
+        assert_eq!(parsed["tier"], "local");
+        assert!(parsed.get("summary").is_none());
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let summary = Summary {
+            text: "## Points\n- Greeting [00:00]".to_string(),
+            model: "claude-sonnet-4-6".to_string(),
+            usage: Default::default(),
+            parts: 1,
+            structured: None,
+        };
+        assert_eq!(
+            render_markdown(&diarized_transcript(), Some(&summary)),
+            "# Test Video\n\n\
+             ## Summary\n\n### Points\n- Greeting [00:00](https://youtu.be/test123?t=0)\n\n\
+             ## Transcript\n\n\
+             - [00:00](https://youtu.be/test123?t=0) **Alice:** Hello world\n\
+             - [00:01](https://youtu.be/test123?t=1) **SPEAKER B:** This is a test\n\
+             - [00:03](https://youtu.be/test123?t=3) Still me"
+        );
+        assert!(!render_markdown(&sample_transcript(), None).contains("## Summary"));
+    }
+
+    #[test]
+    fn test_render_html() {
+        let mut t = sample_transcript();
+        t.title = "Q&A".to_string();
+        let html = render_html(&t, None);
+        assert!(html.contains("<title>Q&amp;A</title>"));
+        assert!(html.contains("<p><a href=\"https://youtu.be/test123?t=1\">00:01</a> This is a test</p>"));
+        assert!(!html.contains("class=\"summary\""));
+        assert!(html.ends_with("</section>\n</body>\n</html>"));
+    }
+
+    #[test]
+    fn test_render_json_with_speakers() {
+        let t = diarized_transcript();
+        let parsed: serde_json::Value = serde_json::from_str(&render_json(&t)).unwrap();
+        assert_eq!(parsed["segments"][0]["speaker"], "A");
+        assert_eq!(parsed["speakers"]["A"], "Alice");
+
+        // Undiarized transcripts don't grow speaker fields
+        let parsed: serde_json::Value = serde_json::from_str(&render_json(&sample_transcript())).unwrap();
+        assert!(parsed["segments"][0].get("speaker").is_none());
+        assert!(parsed.get("speakers").is_none());
+    }
+
+    #[test]
+    fn test_render_json_flags_estimated_timing() {
+        let mut t = sample_transcript();
+        t.segments[1].estimated = true;
+        let parsed: serde_json::Value = serde_json::from_str(&render_json(&t)).unwrap();
+        assert!(parsed["segments"][0].get("estimated").is_none());
+        assert_eq!(parsed["segments"][1]["estimated"], true);
+    }
+
+    #[test]
+    fn test_render_vtt() {
+        let t = diarized_transcript();
+        let expected = "\
+WEBVTT
+
+00:00:00.000 --> 00:00:01.500
+<v Alice>Hello world
+
+00:00:01.500 --> 00:00:03.500
+<v SPEAKER B>This is a test
+
+00:00:03.500 --> 00:00:04.500
+<v SPEAKER B>Still me";
+        assert_eq!(render_vtt(&t), expected);
+    }
+
     #[test]
     fn test_format_srt_time() {
         assert_eq!(format_srt_time(0.0), "00:00:00,000");
@@ -118,14 +461,36 @@ This is a test";
         assert_eq!(format_srt_time(3661.0), "01:01:01,000");
     }
 
+    #[test]
+    fn test_render_summary_markdown() {
+        let markdown = render_summary_markdown("- Setup [01:02, 1:02:03]", "abc123");
+        assert_eq!(
+            markdown,
+            "- Setup [01:02](https://youtu.be/abc123?t=62), [1:02:03](https://youtu.be/abc123?t=3723)"
+        );
+    }
+
+    #[test]
+    fn test_render_summary_html() {
+        let html = render_summary_html("## Key points\n- **Rust** <3 [00:05]\n- Cargo\n\nThat's all.", "abc123");
+        assert_eq!(
+            html,
+            "<h3>Key points</h3>\n<ul>\n<li><strong>Rust</strong> &lt;3 \
+             <a href=\"https://youtu.be/abc123?t=5\">00:05</a></li>\n<li>Cargo</li>\n</ul>\n<p>That's all.</p>"
+        );
+    }
+
     #[test]
     fn test_render_srt_empty() {
         let t = Transcript {
             video_id: "empty".to_string(),
             title: "Empty".to_string(),
             language: "en".to_string(),
+            source_language: None,
             source: TranscriptSource::Caption,
             segments: vec![],
+            speakers: Default::default(),
+            quality: None,
         };
         assert_eq!(render_srt(&t), "");
     }
@@ -0,0 +1,385 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use eyre::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::cost::format_usd;
+use crate::whisper::WhisperModel;
+use crate::youtube::InnerTubeClient;
+
+/// Where a tier gets its transcript from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TierSource {
+    /// A transcript saved by an earlier run
+    Cache,
+    /// YouTube's own caption tracks
+    Captions,
+    /// Caption tracks as one of YouTube's apps sees them
+    AltClient,
+    /// Transcription of the downloaded audio on this machine, with whisper.cpp
+    Local,
+    /// Paid transcription of the downloaded audio
+    Whisper,
+}
+
+impl fmt::Display for TierSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TierSource::Cache => write!(f, "cache"),
+            TierSource::Captions => write!(f, "captions"),
+            TierSource::AltClient => write!(f, "alt_client"),
+            TierSource::Local => write!(f, "local"),
+            TierSource::Whisper => write!(f, "whisper"),
+        }
+    }
+}
+
+/// One step of the fetch policy, tried only when all its conditions hold
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tier {
+    pub source: TierSource,
+    /// Only for videos shorter than this many minutes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_duration_mins: Option<f64>,
+    /// Only for videos at least this many minutes long
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_duration_mins: Option<f64>,
+    /// Only when the video has no captions written by a person
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub without_manual_captions: bool,
+    /// Captions and alt_client: ignore auto-generated tracks
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub manual_only: bool,
+    /// Whisper: skip when the estimated cost is above this many US dollars
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cost: Option<f64>,
+    /// Whisper: transcription model for this tier instead of the default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Alt_client: the app to look the video up as; Android if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<InnerTubeClient>,
+    /// Local: the whisper.cpp ggml model file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_path: Option<PathBuf>,
+    /// Local: the whisper.cpp program to run; `whisper-cli` if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+/// What is known about a video when deciding whether a tier applies.
+/// `None` means the fact couldn't be determined.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Facts {
+    /// Length in seconds
+    pub duration_secs: Option<f64>,
+    pub manual_captions: Option<bool>,
+    /// What the tier would cost to run, in US dollars
+    pub estimated_cost: Option<f64>,
+}
+
+impl Tier {
+    pub fn new(source: TierSource) -> Self {
+        Self {
+            source,
+            max_duration_mins: None,
+            min_duration_mins: None,
+            without_manual_captions: false,
+            manual_only: false,
+            max_cost: None,
+            model: None,
+            client: None,
+            model_path: None,
+            command: None,
+        }
+    }
+
+    /// Whether a condition depends on the video's length
+    pub fn needs_duration(&self) -> bool {
+        self.max_duration_mins.is_some() || self.min_duration_mins.is_some() || self.max_cost.is_some()
+    }
+
+    /// Whether a condition depends on the video's caption tracks
+    pub fn needs_caption_tracks(&self) -> bool {
+        self.without_manual_captions
+    }
+
+    /// The tier's own transcription model, if it overrides the default
+    pub fn whisper_model(&self) -> Result<Option<WhisperModel>> {
+        self.model.as_deref().map(str::parse).transpose()
+    }
+
+    /// The app an alt_client tier looks videos up as
+    pub fn inner_tube_client(&self) -> InnerTubeClient {
+        self.client.unwrap_or(InnerTubeClient::Android)
+    }
+
+    /// The whisper.cpp program a local tier runs
+    pub fn local_command(&self) -> &str {
+        self.command.as_deref().unwrap_or("whisper-cli")
+    }
+
+    /// Why the tier doesn't apply to a video, or `None` if it does. A
+    /// length or cost that couldn't be determined fails its condition;
+    /// captions that couldn't be listed count as none.
+    pub fn skip_reason(&self, facts: &Facts) -> Option<String> {
+        let mins = facts.duration_secs.map(|secs| secs / 60.0);
+        if self.needs_duration() && mins.is_none() {
+            return Some("video length unknown".to_string());
+        }
+        if let (Some(max), Some(mins)) = (self.max_duration_mins, mins)
+            && mins >= max
+        {
+            return Some(format!("{mins:.1} min is not under {max} min"));
+        }
+        if let (Some(min), Some(mins)) = (self.min_duration_mins, mins)
+            && mins < min
+        {
+            return Some(format!("{mins:.1} min is under {min} min"));
+        }
+        if self.without_manual_captions && facts.manual_captions == Some(true) {
+            return Some("video has manual captions".to_string());
+        }
+        if let Some(cap) = self.max_cost {
+            match facts.estimated_cost {
+                Some(cost) if cost > cap => {
+                    return Some(format!(
+                        "estimated cost {} is over {}",
+                        format_usd(cost),
+                        format_usd(cap)
+                    ));
+                }
+                Some(_) => {}
+                None => return Some("cost could not be estimated".to_string()),
+            }
+        }
+        None
+    }
+
+    fn validate(&self) -> Result<()> {
+        let source = self.source;
+        let only = |set: bool, name: &str, allowed: &[TierSource]| {
+            if set && !allowed.contains(&source) {
+                let allowed: Vec<String> = allowed.iter().map(ToString::to_string).collect();
+                bail!("'{name}' only applies to {} tiers, not {source}", allowed.join(" and "));
+            }
+            Ok(())
+        };
+        only(
+            self.manual_only,
+            "manual_only",
+            &[TierSource::Captions, TierSource::AltClient],
+        )?;
+        only(self.max_cost.is_some(), "max_cost", &[TierSource::Whisper])?;
+        only(self.model.is_some(), "model", &[TierSource::Whisper])?;
+        only(self.client.is_some(), "client", &[TierSource::AltClient])?;
+        only(self.model_path.is_some(), "model_path", &[TierSource::Local])?;
+        only(self.command.is_some(), "command", &[TierSource::Local])?;
+        if source == TierSource::Local && self.model_path.is_none() {
+            bail!("local tiers need a 'model_path' to a whisper.cpp model");
+        }
+
+        if source == TierSource::Cache && (self.needs_duration() || self.needs_caption_tracks()) {
+            bail!("cache tiers take no conditions");
+        }
+        if let (Some(min), Some(max)) = (self.min_duration_mins, self.max_duration_mins)
+            && min >= max
+        {
+            bail!("min_duration_mins ({min}) must be below max_duration_mins ({max})");
+        }
+        self.whisper_model()?;
+        Ok(())
+    }
+}
+
+/// The ordered tiers tried for each video until one produces a transcript
+#[derive(Debug, Clone, PartialEq)]
+pub struct Policy {
+    tiers: Vec<Tier>,
+}
+
+impl Default for Policy {
+    /// Cache, then captions, then Whisper, unconditionally
+    fn default() -> Self {
+        Self {
+            tiers: vec![
+                Tier::new(TierSource::Cache),
+                Tier::new(TierSource::Captions),
+                Tier::new(TierSource::Whisper),
+            ],
+        }
+    }
+}
+
+impl Policy {
+    pub fn new(tiers: Vec<Tier>) -> Result<Self> {
+        if tiers.is_empty() {
+            bail!("the fetch policy has no tiers");
+        }
+        for (i, tier) in tiers.iter().enumerate() {
+            tier.validate()
+                .map_err(|e| eyre::eyre!("tier {} ({}): {e}", i + 1, tier.source))?;
+        }
+        Ok(Self { tiers })
+    }
+
+    /// The configured tiers, or the default policy if none are configured
+    pub fn from_config(tiers: &[Tier]) -> Result<Self> {
+        if tiers.is_empty() {
+            return Ok(Self::default());
+        }
+        Self::new(tiers.to_vec())
+    }
+
+    /// Whisper alone, with no conditions
+    pub fn whisper_only() -> Self {
+        Self {
+            tiers: vec![Tier::new(TierSource::Whisper)],
+        }
+    }
+
+    /// The same policy without any `source` tiers
+    pub fn without(self, source: TierSource) -> Result<Self> {
+        Self::new(self.tiers.into_iter().filter(|t| t.source != source).collect())
+    }
+
+    pub fn tiers(&self) -> &[Tier] {
+        &self.tiers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Wrapper {
+        tiers: Vec<Tier>,
+    }
+
+    fn parse(toml_str: &str) -> Vec<Tier> {
+        toml::from_str::<Wrapper>(toml_str).unwrap().tiers
+    }
+
+    fn facts(mins: f64) -> Facts {
+        Facts {
+            duration_secs: Some(mins * 60.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        let tiers = parse(
+            r#"
+[[tiers]]
+source = "captions"
+manual_only = true
+
+[[tiers]]
+source = "whisper"
+max_duration_mins = 60
+without_manual_captions = true
+max_cost = 0.5
+model = "gpt-4o-mini-transcribe"
+"#,
+        );
+        let policy = Policy::new(tiers).unwrap();
+        let tiers = policy.tiers();
+        assert_eq!(tiers.len(), 2);
+        assert!(tiers[0].manual_only);
+        assert_eq!(tiers[1].source, TierSource::Whisper);
+        assert_eq!(tiers[1].max_duration_mins, Some(60.0));
+        assert_eq!(
+            tiers[1].whisper_model().unwrap(),
+            Some(WhisperModel::Gpt4oMiniTranscribe)
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_misplaced_conditions() {
+        let err = Policy::new(parse("[[tiers]]\nsource = \"whisper\"\nmanual_only = true")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "tier 1 (whisper): 'manual_only' only applies to captions and alt_client tiers, not whisper"
+        );
+        assert!(Policy::new(parse("[[tiers]]\nsource = \"captions\"\nclient = \"ios\"")).is_err());
+        assert!(Policy::new(parse("[[tiers]]\nsource = \"local\"")).is_err());
+        assert!(Policy::new(parse("[[tiers]]\nsource = \"cache\"\nmax_duration_mins = 5")).is_err());
+        assert!(Policy::new(parse("[[tiers]]\nsource = \"whisper\"\nmodel = \"nope\"")).is_err());
+        assert!(toml::from_str::<Wrapper>("[[tiers]]\nsource = \"tape\"").is_err());
+        assert!(Policy::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_parse_alt_client_and_local_tiers() {
+        let tiers = parse(
+            r#"
+[[tiers]]
+source = "alt_client"
+client = "ios"
+manual_only = true
+
+[[tiers]]
+source = "alt_client"
+
+[[tiers]]
+source = "local"
+model_path = "/models/ggml-base.en.bin"
+max_duration_mins = 30
+"#,
+        );
+        let policy = Policy::new(tiers).unwrap();
+        let tiers = policy.tiers();
+        assert_eq!(tiers[0].source, TierSource::AltClient);
+        assert_eq!(tiers[0].inner_tube_client(), InnerTubeClient::Ios);
+        assert_eq!(tiers[1].inner_tube_client(), InnerTubeClient::Android);
+        assert_eq!(tiers[2].source, TierSource::Local);
+        assert_eq!(tiers[2].local_command(), "whisper-cli");
+        assert_eq!(tiers[2].source.to_string(), "local");
+        assert_eq!(TierSource::AltClient.to_string(), "alt_client");
+    }
+
+    #[test]
+    fn test_skip_reason_duration() {
+        let mut tier = Tier::new(TierSource::Whisper);
+        tier.max_duration_mins = Some(60.0);
+        assert_eq!(tier.skip_reason(&facts(45.0)), None);
+        assert_eq!(tier.skip_reason(&facts(90.0)).unwrap(), "90.0 min is not under 60 min");
+        assert_eq!(tier.skip_reason(&Facts::default()).unwrap(), "video length unknown");
+    }
+
+    #[test]
+    fn test_skip_reason_captions_and_cost() {
+        let mut tier = Tier::new(TierSource::Whisper);
+        tier.without_manual_captions = true;
+        tier.max_cost = Some(0.25);
+
+        let mut known = facts(10.0);
+        known.estimated_cost = Some(0.06);
+        assert_eq!(tier.skip_reason(&known), None);
+
+        known.manual_captions = Some(true);
+        assert_eq!(tier.skip_reason(&known).unwrap(), "video has manual captions");
+
+        known.manual_captions = None;
+        known.estimated_cost = Some(0.36);
+        assert_eq!(tier.skip_reason(&known).unwrap(), "estimated cost $0.36 is over $0.25");
+    }
+
+    #[test]
+    fn test_without_cache() {
+        let policy = Policy::default().without(TierSource::Cache).unwrap();
+        let sources: Vec<TierSource> = policy.tiers().iter().map(|t| t.source).collect();
+        assert_eq!(sources, vec![TierSource::Captions, TierSource::Whisper]);
+        assert!(
+            Policy::new(vec![Tier::new(TierSource::Cache)])
+                .unwrap()
+                .without(TierSource::Cache)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use std::path::Path;
+
+use eyre::{Result, bail};
+use log::debug;
+use regex::{Captures, Regex};
+use serde::Serialize;
+
+use crate::config::prompts_dir;
+
+/// Template used when none is named
+pub const DEFAULT_PROMPT: &str = "summary";
+
+/// Separates an optional system prompt from the message in a template file
+const SYSTEM_SEPARATOR: &str = "---";
+
+/// System prompt for templates that don't bring their own
+const GENERIC_SYSTEM_PROMPT: &str = "You are a helpful assistant that works with video transcripts.";
+
+const VARIABLES: &[&str] = &[
+    "title",
+    "channel",
+    "video_id",
+    "language",
+    "transcript",
+    "timestamped_transcript",
+];
+
+const BUILTINS: &[(&str, &str)] = &[
+    (
+        "summary",
+        "You are a helpful assistant that summarizes video transcripts. \
+Provide a clear, structured summary that captures the key points, main arguments, and important details. \
+Use bullet points for key takeaways.
+---
+Summarize this transcript from the video \"{{title}}\":
+
+{{transcript}}",
+    ),
+    (
+        "tldr",
+        "You are a helpful assistant that condenses video transcripts.
+---
+Give a TL;DR of the video \"{{title}}\" in two or three sentences, followed by its single most important takeaway.
+
+{{transcript}}",
+    ),
+    (
+        "notes",
+        "You are a meticulous note-taker working from video transcripts.
+---
+Write detailed notes on the video \"{{title}}\". Organize them under headings that follow the video's structure, \
+keep specific names, numbers, examples and definitions, and give the timestamp where each topic starts.
+
+{{timestamped_transcript}}",
+    ),
+    (
+        "wisdom",
+        "You extract the most valuable insights from video transcripts.
+---
+From the video \"{{title}}\" by {{channel}}, extract:
+- IDEAS: the most surprising and insightful ideas
+- QUOTES: the best quotes, verbatim
+- HABITS: practices the speakers follow or recommend
+- FACTS: notable facts, figures and references
+- RECOMMENDATIONS: concrete advice
+Write each item as one concise bullet under its heading.
+
+{{transcript}}",
+    ),
+    (
+        "action-items",
+        "You turn video transcripts into task lists.
+---
+List the action items in the video \"{{title}}\": every task, recommendation or next step for the viewer or the \
+speakers. Write each as a checkbox line (\"- [ ] ...\") starting with a verb, and name the owner when the \
+transcript does.
+
+{{transcript}}",
+    ),
+];
+
+/// Values substituted into a template
+#[derive(Debug, Clone, Default)]
+pub struct Variables<'a> {
+    pub title: &'a str,
+    pub channel: Option<&'a str>,
+    pub video_id: &'a str,
+    pub language: &'a str,
+    pub transcript: &'a str,
+    pub timestamped_transcript: &'a str,
+}
+
+impl Variables<'_> {
+    fn get(&self, name: &str) -> &str {
+        match name {
+            "title" => self.title,
+            "channel" => self.channel.unwrap_or("an unknown channel"),
+            "video_id" => self.video_id,
+            "language" => self.language,
+            "transcript" => self.transcript,
+            "timestamped_transcript" => self.timestamped_transcript,
+            _ => "",
+        }
+    }
+}
+
+/// A summarization prompt with `{{variable}}` placeholders
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Template {
+    pub name: String,
+    system: String,
+    message: String,
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Self::builtin(DEFAULT_PROMPT).expect("default prompt is built in")
+    }
+}
+
+impl Template {
+    /// A template shipped with ytx
+    pub fn builtin(name: &str) -> Option<Self> {
+        BUILTINS
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+            .map(|(name, content)| Self::parse(name, content).expect("built-in prompts are valid"))
+    }
+
+    /// The template called `name` in ~/.config/ytx/prompts/ (as `NAME.md`
+    /// or `NAME.txt`), or else the built-in one
+    pub fn load(name: &str) -> Result<Self> {
+        let dir = prompts_dir();
+        for ext in ["md", "txt"] {
+            let path = dir.join(format!("{name}.{ext}"));
+            if path.exists() {
+                return Self::from_file(&path);
+            }
+        }
+        Self::builtin(name).ok_or_else(|| {
+            eyre::eyre!(
+                "unknown prompt '{name}' (built in: {}; or add {name}.md to {})",
+                BUILTINS.iter().map(|(n, _)| *n).collect::<Vec<_>>().join(", "),
+                dir.display()
+            )
+        })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        debug!("Loading prompt template from {}", path.display());
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| eyre::eyre!("could not read prompt template {}: {e}", path.display()))?;
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        Self::parse(&name, &content).map_err(|e| eyre::eyre!("{}: {e}", path.display()))
+    }
+
+    /// Text above the first `---` line is the system prompt, the rest the
+    /// message; later `---` lines are Markdown rules in the message, so a
+    /// template with no system prompt that uses one starts with `---`. A
+    /// message that never mentions the transcript gets it appended.
+    fn parse(name: &str, content: &str) -> Result<Self> {
+        let content = content.replace("\r\n", "\n");
+        let (system, message) = split_system(&content);
+        let system = match system {
+            Some(system) if !system.is_empty() => system,
+            _ => GENERIC_SYSTEM_PROMPT,
+        };
+        if message.is_empty() {
+            bail!("prompt template is empty");
+        }
+
+        for caps in placeholder().captures_iter(message) {
+            if !VARIABLES.contains(&&caps[1]) {
+                bail!(
+                    "unknown variable {{{{{}}}}} (expected one of: {})",
+                    &caps[1],
+                    VARIABLES.join(", ")
+                );
+            }
+        }
+
+        let mut template = Self {
+            name: name.to_string(),
+            system: system.to_string(),
+            message: message.to_string(),
+        };
+        if !template.uses("transcript") && !template.uses("timestamped_transcript") {
+            template.message.push_str("\n\n{{transcript}}");
+        }
+        Ok(template)
+    }
+
+    pub fn system(&self) -> &str {
+        &self.system
+    }
+
+    /// Whether the message refers to `variable`
+    pub fn uses(&self, variable: &str) -> bool {
+        placeholder()
+            .captures_iter(&self.message)
+            .any(|caps| &caps[1] == variable)
+    }
+
+    /// The message with every placeholder filled in. Substitution is a
+    /// single pass, so braces in the transcript are left alone.
+    pub fn render(&self, vars: &Variables) -> String {
+        placeholder()
+            .replace_all(&self.message, |caps: &Captures| vars.get(&caps[1]).to_string())
+            .into_owned()
+    }
+}
+
+/// The text above the first `---` line, if there is one, and below it
+fn split_system(content: &str) -> (Option<&str>, &str) {
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        if line.trim_end() == SYSTEM_SEPARATOR {
+            let message = &content[offset + line.len()..];
+            return (Some(content[..offset].trim()), message.trim());
+        }
+        offset += line.len();
+    }
+    (None, content.trim())
+}
+
+fn placeholder() -> Regex {
+    Regex::new(r"\{\{\s*(\w+)\s*\}\}").expect("valid regex")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtins_parse() {
+        for (name, _) in BUILTINS {
+            let template = Template::builtin(name).unwrap();
+            assert!(!template.system().is_empty());
+        }
+        assert!(
+            Template::default()
+                .system()
+                .starts_with("You are a helpful assistant that summarizes")
+        );
+        assert!(Template::builtin("notes").unwrap().uses("timestamped_transcript"));
+    }
+
+    #[test]
+    fn test_parse_and_render() {
+        let template = Template::parse(
+            "quiz",
+            "You write quizzes.\n---\nWrite a quiz in {{ language }} on \"{{title}}\":\n\n{{transcript}}",
+        )
+        .unwrap();
+        assert_eq!(template.system(), "You write quizzes.");
+
+        let vars = Variables {
+            title: "Rust {{title}}",
+            language: "de",
+            transcript: "Hello {{channel}}",
+            ..Default::default()
+        };
+        assert_eq!(
+            template.render(&vars),
+            "Write a quiz in de on \"Rust {{title}}\":\n\nHello {{channel}}"
+        );
+    }
+
+    #[test]
+    fn test_parse_appends_transcript() {
+        let template = Template::parse("short", "List the tools mentioned.\n").unwrap();
+        assert_eq!(template.system(), GENERIC_SYSTEM_PROMPT);
+        let vars = Variables {
+            transcript: "We use cargo.",
+            ..Default::default()
+        };
+        assert_eq!(template.render(&vars), "List the tools mentioned.\n\nWe use cargo.");
+    }
+
+    #[test]
+    fn test_parse_separator_lines() {
+        // Only the first `---` line separates; later ones are Markdown rules
+        let template =
+            Template::parse("crlf", "You take notes.\r\n---\r\n# Notes\r\n\r\n---\r\n{{transcript}}").unwrap();
+        assert_eq!(template.system(), "You take notes.");
+        assert_eq!(template.message, "# Notes\n\n---\n{{transcript}}");
+
+        // A leading separator keeps a rule in a template without a system prompt
+        let template = Template::parse("rule", "---\n# Notes\n\n---\n\n{{transcript}}").unwrap();
+        assert_eq!(template.system(), GENERIC_SYSTEM_PROMPT);
+        assert_eq!(template.message, "# Notes\n\n---\n\n{{transcript}}");
+
+        // Dashes within a line aren't a separator
+        let template = Template::parse("dashes", "Summarize --- briefly.").unwrap();
+        assert_eq!(template.system(), GENERIC_SYSTEM_PROMPT);
+        assert!(template.message.starts_with("Summarize --- briefly."));
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_variable() {
+        let err = Template::parse("typo", "Summarize {{titel}}").unwrap_err();
+        assert!(err.to_string().starts_with("unknown variable {{titel}}"));
+        assert!(Template::parse("empty", "system\n---\n  \n").is_err());
+    }
+
+    #[test]
+    fn test_channel_fallback() {
+        let template = Template::builtin("wisdom").unwrap();
+        assert!(template.uses("channel"));
+        let rendered = template.render(&Variables::default());
+        assert!(rendered.contains("by an unknown channel"));
+    }
+}
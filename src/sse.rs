@@ -0,0 +1,101 @@
+/// Splits a server-sent event stream into the data of each event, however
+/// the body happens to be chunked
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buf: Vec<u8>,
+    data: Vec<String>,
+    /// Newline-delimited JSON: every non-empty line is a whole event
+    ndjson: bool,
+}
+
+impl SseParser {
+    /// A parser for newline-delimited JSON streams, as Ollama sends
+    pub fn ndjson() -> Self {
+        Self {
+            ndjson: true,
+            ..Default::default()
+        }
+    }
+
+    /// Feed the next chunk of the body, returning the data of every event
+    /// it completes
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buf.extend_from_slice(chunk);
+        let mut events = Vec::new();
+
+        // Lines are only decoded once complete, so a multi-byte character
+        // split across chunks survives
+        while let Some(pos) = self.buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if self.ndjson {
+                events.extend((!line.trim().is_empty()).then(|| line.to_string()));
+            } else if line.is_empty() {
+                events.extend(self.take_event());
+            } else if let Some(value) = line.strip_prefix("data:") {
+                self.data.push(value.strip_prefix(' ').unwrap_or(value).to_string());
+            }
+            // `event:` names are ignored: both providers repeat the type in the data
+        }
+        events
+    }
+
+    /// The data of an event left unterminated when the stream ended
+    pub fn finish(&mut self) -> Option<String> {
+        if !self.buf.is_empty() {
+            let events = self.push(b"\n");
+            if self.ndjson {
+                return events.into_iter().next();
+            }
+        }
+        self.take_event()
+    }
+
+    fn take_event(&mut self) -> Option<String> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let data = self.data.join("\n");
+        self.data.clear();
+        Some(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_across_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"event: ping\ndata: {\"a\"").is_empty());
+        assert_eq!(parser.push(b":1}\n\ndata: two\r\n\r\n"), vec!["{\"a\":1}", "two"]);
+    }
+
+    #[test]
+    fn test_multiline_data_and_comments() {
+        let mut parser = SseParser::default();
+        let events = parser.push(b": keep-alive\ndata: first\ndata:second\n\n");
+        assert_eq!(events, vec!["first\nsecond"]);
+    }
+
+    #[test]
+    fn test_ndjson() {
+        let mut parser = SseParser::ndjson();
+        assert_eq!(parser.push(b"{\"a\":1}\n\n{\"b\""), vec!["{\"a\":1}"]);
+        assert!(parser.push(b":2}").is_empty());
+        assert_eq!(parser.finish().as_deref(), Some("{\"b\":2}"));
+    }
+
+    #[test]
+    fn test_split_utf8_and_unterminated_event() {
+        let mut parser = SseParser::default();
+        let bytes = "data: caf\u{e9}".as_bytes();
+        assert!(parser.push(&bytes[..bytes.len() - 1]).is_empty());
+        assert!(parser.push(&bytes[bytes.len() - 1..]).is_empty());
+        assert_eq!(parser.finish().as_deref(), Some("café"));
+        assert_eq!(parser.finish(), None);
+    }
+}
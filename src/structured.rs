@@ -0,0 +1,155 @@
+use eyre::{Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::cite;
+
+/// Tells the model what a structured summary holds. The schema is sent
+/// too, but not every provider can enforce it.
+pub const STRUCTURED_INSTRUCTIONS: &str = "Reply with only a JSON object, no other text, with these fields:
+- tldr: two or three sentences on what the video is about and what it concludes
+- key_points: the main points, one sentence each
+- quotes: notable quotes, word for word, each with text, speaker (null if unknown) and timestamp, \
+the [mm:ss] or [h:mm:ss] time of the transcript line it starts on, without brackets
+- people: people mentioned or speaking
+- tools: tools, products, libraries and services mentioned
+- action_items: tasks or next steps for the viewer or the speakers, each starting with a verb
+Use empty lists where there is nothing to report.";
+
+/// A summary in a fixed shape for other tools to consume
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StructuredSummary {
+    pub tldr: String,
+    pub key_points: Vec<String>,
+    pub quotes: Vec<Quote>,
+    pub people: Vec<String>,
+    pub tools: Vec<String>,
+    pub action_items: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Quote {
+    pub text: String,
+    pub speaker: Option<String>,
+    /// `mm:ss` or `h:mm:ss`
+    pub timestamp: String,
+    /// Link to the quote in the video, filled in by `link`
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl StructuredSummary {
+    /// The JSON schema replies must match, in the strict form OpenAI
+    /// requires: every field required, nothing extra allowed
+    pub fn schema() -> Value {
+        let strings = serde_json::json!({"type": "array", "items": {"type": "string"}});
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "tldr": {"type": "string"},
+                "key_points": strings,
+                "quotes": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "text": {"type": "string"},
+                            "speaker": {"type": ["string", "null"]},
+                            "timestamp": {"type": "string"}
+                        },
+                        "required": ["text", "speaker", "timestamp"],
+                        "additionalProperties": false
+                    }
+                },
+                "people": strings,
+                "tools": strings,
+                "action_items": strings
+            },
+            "required": ["tldr", "key_points", "quotes", "people", "tools", "action_items"],
+            "additionalProperties": false
+        })
+    }
+
+    /// Parse and check a model's reply, allowing for a Markdown code fence
+    /// around it
+    pub fn parse(reply: &str) -> Result<Self> {
+        let json = reply.trim();
+        let json = json
+            .strip_prefix("```json")
+            .or_else(|| json.strip_prefix("```"))
+            .and_then(|rest| rest.strip_suffix("```"))
+            .unwrap_or(json);
+
+        let summary: Self = serde_json::from_str(json)?;
+        if summary.tldr.trim().is_empty() {
+            bail!("tldr is empty");
+        }
+        for quote in &summary.quotes {
+            if cite::seconds(quote.timestamp.trim_matches(['[', ']'])).is_none() {
+                bail!("quote timestamp '{}' is not mm:ss or h:mm:ss", quote.timestamp);
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Fill in each quote's link into the video
+    pub fn link(&mut self, video_id: &str) {
+        for quote in &mut self.quotes {
+            let secs = cite::seconds(quote.timestamp.trim_matches(['[', ']']));
+            quote.url = secs.map(|secs| cite::video_url(video_id, secs));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPLY: &str = r#"{
+        "tldr": "A tour of Rust's ownership model.",
+        "key_points": ["Every value has one owner"],
+        "quotes": [{"text": "Borrowing is the point.", "speaker": null, "timestamp": "12:34"}],
+        "people": ["Ferris"],
+        "tools": ["cargo", "clippy"],
+        "action_items": ["Read the borrow checker chapter"]
+    }"#;
+
+    #[test]
+    fn test_parse_and_link() {
+        let mut summary = StructuredSummary::parse(&format!("```json\n{REPLY}\n```")).unwrap();
+        assert_eq!(summary.tools, vec!["cargo", "clippy"]);
+
+        summary.link("abc123");
+        assert_eq!(summary.quotes[0].url.as_deref(), Some("https://youtu.be/abc123?t=754"));
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["quotes"][0]["url"], "https://youtu.be/abc123?t=754");
+    }
+
+    #[test]
+    fn test_parse_rejects_schema_violations() {
+        assert!(StructuredSummary::parse(r#"{"tldr": "Missing the rest"}"#).is_err());
+        let extra = REPLY.replacen("\"people\"", "\"mood\": \"upbeat\", \"people\"", 1);
+        assert!(StructuredSummary::parse(&extra).is_err());
+        let bad_time = REPLY.replace("12:34", "about halfway");
+        assert_eq!(
+            StructuredSummary::parse(&bad_time).unwrap_err().to_string(),
+            "quote timestamp 'about halfway' is not mm:ss or h:mm:ss"
+        );
+    }
+
+    #[test]
+    fn test_schema_requires_every_field() {
+        let schema = StructuredSummary::schema();
+        let required: Vec<&str> = schema["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(Value::as_str)
+            .collect();
+        let properties = schema["properties"].as_object().unwrap();
+        assert_eq!(required.len(), properties.len());
+        assert!(required.iter().all(|field| properties.contains_key(*field)));
+    }
+}
@@ -1,199 +1,654 @@
-use eyre::{Result, bail};
+use std::collections::BTreeMap;
+
+use eyre::Result;
 use log::debug;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::cite::{CITE_INSTRUCTIONS, KEEP_CITATIONS, stamp, timestamped_text};
+use crate::llm::{Llm, Provider, Reply, TokenUsage};
+use crate::prompt::{Template, Variables};
+use crate::structured::{STRUCTURED_INSTRUCTIONS, StructuredSummary};
+use crate::{Chapter, PaidFailure, Segment, Transcript};
+
+/// Transcript tokens sent in one request, by model prefix: about half the
+/// context window, leaving room for the prompt and the reply
+const CHUNK_TOKENS: &[(&str, usize)] = &[
+    ("claude", 100_000),
+    ("gpt-4.1", 400_000),
+    ("gpt-5", 150_000),
+    ("gpt-4o", 60_000),
+    ("o3", 100_000),
+    ("o4", 100_000),
+    ("gemini", 400_000),
+    ("mistral", 60_000),
+];
+
+/// Chunk size for models not in `CHUNK_TOKENS`
+const DEFAULT_CHUNK_TOKENS: usize = 30_000;
+
+/// Chunk size for models served by Ollama, whose context defaults small
+/// and costs local memory to raise
+const OLLAMA_CHUNK_TOKENS: usize = 8_000;
+
+const PART_SYSTEM_PROMPT: &str = "You are a helpful assistant that summarizes one part of a long video transcript. \
+Capture the key points, arguments, names and figures in this part concisely; \
+your summary will be combined with summaries of the other parts.";
 
-use crate::Transcript;
+/// An LLM-generated summary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Summary {
+    pub text: String,
+    pub model: String,
+    /// Tokens billed across every request that went into the summary
+    pub usage: TokenUsage,
+    /// How many parts the transcript was summarized in (1 if it fit whole)
+    pub parts: usize,
+    /// The summary as JSON in a fixed shape, when asked for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured: Option<StructuredSummary>,
+}
 
-const DEFAULT_SYSTEM_PROMPT: &str = "You are a helpful assistant that summarizes video transcripts. \
-Provide a clear, structured summary that captures the key points, main arguments, and important details. \
-Use bullet points for key takeaways.";
+/// Rough token count for `text`, at about four characters a token
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
 
-/// Summarize a transcript using an LLM
-pub async fn summarize(client: &reqwest::Client, transcript: &Transcript, model: &str) -> Result<String> {
-    let transcript_text = transcript
-        .segments
+/// Transcript tokens to send in one request: the longest prefix in
+/// `overrides` (from config) matching the model, with or without its
+/// `provider:`, else the built-in default
+pub fn chunk_tokens(llm: &Llm, overrides: &BTreeMap<String, usize>) -> usize {
+    let qualified = llm.to_string();
+    let builtin = match llm.provider {
+        Provider::Ollama => OLLAMA_CHUNK_TOKENS,
+        _ => CHUNK_TOKENS
+            .iter()
+            .find(|(prefix, _)| llm.model.starts_with(prefix))
+            .map(|&(_, tokens)| tokens)
+            .unwrap_or(DEFAULT_CHUNK_TOKENS),
+    };
+    overrides
         .iter()
-        .map(|s| s.text.as_str())
-        .collect::<Vec<_>>()
-        .join(" ");
+        .filter(|(prefix, _)| llm.model.starts_with(prefix.as_str()) || qualified.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, &tokens)| tokens)
+        .unwrap_or(builtin)
+}
 
-    if is_anthropic_model(model) {
-        summarize_anthropic(client, &transcript_text, &transcript.title, model).await
-    } else {
-        summarize_openai(client, &transcript_text, &transcript.title, model).await
-    }
+/// Whether a transcript is too long to summarize in one request of
+/// `max_tokens`
+pub fn needs_split(transcript: &Transcript, max_tokens: usize) -> bool {
+    transcript.segments.iter().map(segment_tokens).sum::<usize>() > max_tokens
 }
 
-fn is_anthropic_model(model: &str) -> bool {
-    model.starts_with("claude")
+/// Bump when prompts or splitting change, so summaries made the old way
+/// aren't served from the cache
+const SUMMARY_CACHE_VERSION: u32 = 1;
+
+/// Key a summary is cached under: a hash of the transcript and everything
+/// that shapes the summary, from the model to the prompt and its options
+pub fn cache_key(transcript: &Transcript, opts: &SummaryOptions) -> String {
+    let segments: Vec<(f64, &str)> = transcript.segments.iter().map(|s| (s.start, s.text.as_str())).collect();
+    let inputs = serde_json::json!({
+        "version": SUMMARY_CACHE_VERSION,
+        "title": transcript.title,
+        "language": transcript.language,
+        "segments": segments,
+        "model": opts.llm.to_string(),
+        "base_url": opts.llm.base_url(),
+        "template": opts.template,
+        "max_tokens": opts.max_tokens,
+        "chapters": opts.chapters,
+        "channel": opts.channel,
+        "cite": opts.cite,
+        "structured": opts.structured,
+    });
+    let digest = Sha256::digest(inputs.to_string());
+    digest[..8].iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// How to summarize a transcript
+#[derive(Debug, Clone, Copy)]
+pub struct SummaryOptions<'a> {
+    pub llm: &'a Llm,
+    pub template: &'a Template,
+    /// Transcript tokens to send in one request
+    pub max_tokens: usize,
+    /// Where to split a transcript over `max_tokens`, if the video has chapters
+    pub chapters: &'a [Chapter],
+    pub channel: Option<&'a str>,
+    /// Write the reply to stdout as it arrives (only the final request's,
+    /// when the transcript is summarized in parts)
+    pub stream: bool,
+    /// Send the transcript with `[mm:ss]` times and ask for each point to
+    /// cite the ones behind it
+    pub cite: bool,
+    /// Ask for a `StructuredSummary` instead of free text
+    pub structured: bool,
 }
 
-async fn summarize_anthropic(
+/// Summarize a transcript using an LLM, following the prompt template.
+/// Transcripts over `max_tokens` are split at chapter or segment
+/// boundaries and each part summarized; the template is then applied to
+/// the part summaries in a final request. If a request fails after others
+/// went through, the error is a [`PaidFailure`] with the tokens they used.
+pub async fn summarize(
     client: &reqwest::Client,
-    transcript_text: &str,
-    title: &str,
-    model: &str,
-) -> Result<String> {
-    let api_key = std::env::var("ANTHROPIC_API_KEY").map_err(|_| {
-        eyre::eyre!("ANTHROPIC_API_KEY environment variable not set (required for Claude summarization)")
-    })?;
+    transcript: &Transcript,
+    opts: &SummaryOptions<'_>,
+) -> Result<Summary> {
+    let llm = opts.llm;
+    let template = opts.template;
+    let title = &transcript.title;
+    let with_citations = |system: &str, instructions: &str| {
+        if opts.cite {
+            format!("{system}\n\n{instructions}")
+        } else {
+            system.to_string()
+        }
+    };
+    let vars = Variables {
+        title,
+        channel: opts.channel,
+        video_id: &transcript.video_id,
+        language: &transcript.language,
+        ..Default::default()
+    };
 
-    debug!("Summarizing via Anthropic API with model {model}");
+    if !needs_split(transcript, opts.max_tokens) {
+        // Quotes in a structured summary need the times too
+        let timed = opts.cite || opts.structured;
+        let timestamped = if template.uses("timestamped_transcript") || timed {
+            timestamped_text(&transcript.segments)
+        } else {
+            String::new()
+        };
+        // Citing needs the times whichever transcript the template asks for
+        let transcript_text = if timed {
+            timestamped.clone()
+        } else {
+            transcript
+                .segments
+                .iter()
+                .map(|s| s.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let user_message = template.render(&Variables {
+            transcript: &transcript_text,
+            timestamped_transcript: &timestamped,
+            ..vars
+        });
+        let system = with_citations(template.system(), CITE_INSTRUCTIONS);
+        let (text, structured, usage) = finish(client, opts, &system, &user_message).await?;
+        return Ok(Summary {
+            text,
+            model: llm.model.clone(),
+            usage,
+            parts: 1,
+            structured,
+        });
+    }
 
-    let user_message = format!("Summarize this transcript from the video \"{title}\":\n\n{transcript_text}");
+    let max_tokens = opts.max_tokens;
+    let parts = split_transcript(
+        &transcript.segments,
+        opts.chapters,
+        max_tokens,
+        opts.cite || opts.structured,
+    );
+    debug!(
+        "Transcript exceeds {max_tokens} tokens, summarizing in {} parts",
+        parts.len()
+    );
 
-    let body = serde_json::json!({
-        "model": model,
-        "max_tokens": 4096,
-        "system": DEFAULT_SYSTEM_PROMPT,
-        "messages": [
-            {
-                "role": "user",
-                "content": user_message
-            }
-        ]
-    });
+    let part_system = with_citations(PART_SYSTEM_PROMPT, CITE_INSTRUCTIONS);
+    let combine_system = with_citations(PART_SYSTEM_PROMPT, KEEP_CITATIONS);
+    let mut usage = TokenUsage::default();
+    let mut partials = Vec::with_capacity(parts.len());
+    for (i, part) in parts.iter().enumerate() {
+        let user_message = format!(
+            "Summarize part {} of {} ({}) of the transcript of the video \"{title}\":\n\n{}",
+            i + 1,
+            parts.len(),
+            part.label(),
+            part.text
+        );
+        let (text, part_usage) = llm
+            .complete(client, &part_system, &user_message, Reply::Text)
+            .await
+            .map_err(|e| paid(e, usage))?;
+        usage += part_usage;
+        partials.push(Part { text, ..part.clone() });
+    }
 
-    let resp = client
-        .post("https://api.anthropic.com/v1/messages")
-        .header("x-api-key", &api_key)
-        .header("anthropic-version", "2023-06-01")
-        .header("Content-Type", "application/json")
-        .json(&body)
-        .send()
-        .await?;
+    // Combine in rounds until the part summaries fit in one request
+    while combined_tokens(&partials) > max_tokens && partials.len() > 2 {
+        let mut merged = Vec::new();
+        for batch in batch_parts(&partials, max_tokens) {
+            let prompt = combine_prompt(title, batch);
+            let (text, batch_usage) = llm
+                .complete(client, &combine_system, &prompt, Reply::Text)
+                .await
+                .map_err(|e| paid(e, usage))?;
+            usage += batch_usage;
+            merged.push(Part::merge(batch, text));
+        }
+        debug!("Combined {} part summaries into {}", partials.len(), merged.len());
+        partials = merged;
+    }
+
+    // Part labels carry their time ranges, so one text serves for both
+    let summaries = format_parts(&partials);
+    let user_message = format!(
+        "The transcript is too long to include whole, so summaries of its consecutive parts stand in for it.\n\n{}",
+        template.render(&Variables {
+            transcript: &summaries,
+            timestamped_transcript: &summaries,
+            ..vars
+        })
+    );
+    let system = with_citations(template.system(), KEEP_CITATIONS);
+    let (text, structured, final_usage) = finish(client, opts, &system, &user_message)
+        .await
+        .map_err(|e| paid(e, usage))?;
+    usage += final_usage;
+
+    Ok(Summary {
+        text,
+        model: llm.model.clone(),
+        usage,
+        parts: parts.len(),
+        structured,
+    })
+}
 
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let body = resp.text().await.unwrap_or_default();
-        bail!("Anthropic API returned {status}: {body}");
+/// `error` from a request made after others that used `usage`, adding
+/// whatever the failed step itself had spent
+fn paid(error: eyre::Report, mut usage: TokenUsage) -> eyre::Report {
+    if let Some(&spent) = PaidFailure::<TokenUsage>::spent(&error) {
+        usage += spent;
     }
+    PaidFailure::wrap(error, usage)
+}
+
+/// Make the request that produces the summary itself. A structured reply
+/// that doesn't match the schema is asked for once more, with the error;
+/// if that fails too, the error carries the tokens both requests used.
+async fn finish(
+    client: &reqwest::Client,
+    opts: &SummaryOptions<'_>,
+    system: &str,
+    user_message: &str,
+) -> Result<(String, Option<StructuredSummary>, TokenUsage)> {
+    let llm = opts.llm;
+    if !opts.structured {
+        let reply = if opts.stream { Reply::Stream } else { Reply::Text };
+        let (text, usage) = llm.complete(client, system, user_message, reply).await?;
+        return Ok((text, None, usage));
+    }
+
+    let system = format!("{system}\n\n{STRUCTURED_INSTRUCTIONS}");
+    let schema = StructuredSummary::schema();
+    let (text, mut usage) = llm
+        .complete(client, &system, user_message, Reply::Json(&schema))
+        .await?;
+    let error = match StructuredSummary::parse(&text) {
+        Ok(summary) => return Ok((text, Some(summary), usage)),
+        Err(e) => e,
+    };
 
-    let json: serde_json::Value = resp.json().await?;
-    extract_anthropic_text(&json)
+    debug!("Summary didn't match the schema ({error}), asking again");
+    let retry = format!(
+        "{user_message}\n\nA previous reply did not match the required JSON format: {error}\n\
+         Reply with only a JSON object that does."
+    );
+    let (text, retry_usage) = llm
+        .complete(client, &system, &retry, Reply::Json(&schema))
+        .await
+        .map_err(|e| paid(e, usage))?;
+    usage += retry_usage;
+    let summary = StructuredSummary::parse(&text).map_err(|e| {
+        paid(
+            eyre::eyre!("the summary did not match the JSON schema, even when asked again: {e}"),
+            usage,
+        )
+    })?;
+    Ok((text, Some(summary), usage))
 }
 
-fn extract_anthropic_text(json: &serde_json::Value) -> Result<String> {
-    if let Some(content) = json.get("content").and_then(|c| c.as_array()) {
-        let text: String = content
-            .iter()
-            .filter_map(|block| {
-                if block.get("type")?.as_str()? == "text" {
-                    block.get("text")?.as_str().map(|s| s.to_string())
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>()
-            .join("");
-        if !text.is_empty() {
-            return Ok(text);
+/// A stretch of the transcript, or the summary of one
+#[derive(Debug, Clone, PartialEq)]
+struct Part {
+    start: f64,
+    end: f64,
+    /// Chapters the part covers, if the video has any
+    titles: Vec<String>,
+    text: String,
+}
+
+impl Part {
+    /// A part of `segments`' text, as timed lines if `stamped`
+    fn new(segments: &[&Segment], titles: Vec<String>, stamped: bool) -> Self {
+        let start = segments.first().map(|s| s.start).unwrap_or_default();
+        let end = segments.last().map(|s| s.start + s.duration).unwrap_or_default();
+        let text = if stamped {
+            segments
+                .iter()
+                .map(|s| format!("{} {}", stamp(s.start), s.text))
+                .collect::<Vec<_>>()
+                .join("\n")
+        } else {
+            segments.iter().map(|s| s.text.as_str()).collect::<Vec<_>>().join(" ")
+        };
+        Self {
+            start,
+            end,
+            titles,
+            text,
+        }
+    }
+
+    /// A part covering all of `parts`, with `text` in place of theirs
+    fn merge(parts: &[Part], text: String) -> Self {
+        let mut titles: Vec<String> = parts.iter().flat_map(|p| p.titles.iter().cloned()).collect();
+        titles.dedup();
+        Self {
+            start: parts.first().map(|p| p.start).unwrap_or_default(),
+            end: parts.last().map(|p| p.end).unwrap_or_default(),
+            titles,
+            text,
+        }
+    }
+
+    fn label(&self) -> String {
+        let range = format!("{}-{}", clock(self.start), clock(self.end));
+        if self.titles.is_empty() {
+            range
+        } else {
+            format!("{range}: {}", self.titles.join(", "))
         }
     }
-    bail!("unexpected Anthropic API response format");
 }
 
-async fn summarize_openai(client: &reqwest::Client, transcript_text: &str, title: &str, model: &str) -> Result<String> {
-    let api_key = std::env::var("OPENAI_API_KEY")
-        .map_err(|_| eyre::eyre!("OPENAI_API_KEY environment variable not set (required for OpenAI summarization)"))?;
+fn segment_tokens(segment: &Segment) -> usize {
+    estimate_tokens(&segment.text) + 1
+}
+
+/// Split segments into parts of at most `max_tokens`, keeping chapters
+/// whole where they fit and otherwise cutting between segments
+fn split_transcript(segments: &[Segment], chapters: &[Chapter], max_tokens: usize, stamped: bool) -> Vec<Part> {
+    let mut parts = Vec::new();
+    let mut current: Vec<&Segment> = Vec::new();
+    let mut titles = Vec::new();
+    let mut tokens = 0;
 
-    debug!("Summarizing via OpenAI API with model {model}");
+    for (title, section) in sections(segments, chapters) {
+        let section_tokens: usize = section.iter().map(segment_tokens).sum();
+        if tokens + section_tokens > max_tokens && !current.is_empty() {
+            parts.push(Part::new(&current, std::mem::take(&mut titles), stamped));
+            current.clear();
+            tokens = 0;
+        }
 
-    let user_message = format!("Summarize this transcript from the video \"{title}\":\n\n{transcript_text}");
+        if section_tokens <= max_tokens {
+            current.extend(section);
+            titles.extend(title.map(str::to_string));
+            tokens += section_tokens;
+            continue;
+        }
 
-    let body = serde_json::json!({
-        "model": model,
-        "messages": [
-            {
-                "role": "system",
-                "content": DEFAULT_SYSTEM_PROMPT
-            },
-            {
-                "role": "user",
-                "content": user_message
+        // A chapter too long for one request is cut between segments
+        for segment in section {
+            let cost = segment_tokens(segment);
+            if tokens + cost > max_tokens && !current.is_empty() {
+                parts.push(Part::new(
+                    &current,
+                    title.map(str::to_string).into_iter().collect(),
+                    stamped,
+                ));
+                current.clear();
+                tokens = 0;
             }
-        ]
-    });
+            current.push(segment);
+            tokens += cost;
+        }
+        titles.extend(title.map(str::to_string));
+    }
 
-    let resp = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .bearer_auth(&api_key)
-        .header("Content-Type", "application/json")
-        .json(&body)
-        .send()
-        .await?;
+    if !current.is_empty() {
+        parts.push(Part::new(&current, titles, stamped));
+    }
+    parts
+}
+
+/// Segments grouped by chapter, or all together if there are no chapters.
+/// Segments before the first chapter count as part of it.
+fn sections<'a>(segments: &'a [Segment], chapters: &'a [Chapter]) -> Vec<(Option<&'a str>, &'a [Segment])> {
+    if chapters.is_empty() {
+        return vec![(None, segments)];
+    }
 
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let body = resp.text().await.unwrap_or_default();
-        bail!("OpenAI API returned {status}: {body}");
+    let mut out = Vec::with_capacity(chapters.len());
+    let mut from = 0;
+    for (i, chapter) in chapters.iter().enumerate() {
+        let to = match chapters.get(i + 1) {
+            Some(next) => from + segments[from..].partition_point(|s| s.start < next.start_time),
+            None => segments.len(),
+        };
+        if to > from {
+            out.push((Some(chapter.title.as_str()), &segments[from..to]));
+        }
+        from = to;
     }
+    out
+}
 
-    let json: serde_json::Value = resp.json().await?;
-    extract_openai_text(&json)
+fn combined_tokens(parts: &[Part]) -> usize {
+    parts.iter().map(|p| estimate_tokens(&p.text)).sum()
 }
 
-fn extract_openai_text(json: &serde_json::Value) -> Result<String> {
-    if let Some(text) = json
-        .get("choices")
-        .and_then(|c| c.get(0))
-        .and_then(|c| c.get("message"))
-        .and_then(|m| m.get("content"))
-        .and_then(|t| t.as_str())
-    {
-        return Ok(text.to_string());
+/// Consecutive runs of part summaries that each fit in `max_tokens`, at
+/// least two to a run so every round shrinks the list
+fn batch_parts(parts: &[Part], max_tokens: usize) -> Vec<&[Part]> {
+    let mut batches = Vec::new();
+    let mut from = 0;
+    while from < parts.len() {
+        let mut to = from;
+        let mut tokens = 0;
+        while to < parts.len() && (to - from < 2 || tokens + estimate_tokens(&parts[to].text) <= max_tokens) {
+            tokens += estimate_tokens(&parts[to].text);
+            to += 1;
+        }
+        batches.push(&parts[from..to]);
+        from = to;
     }
-    bail!("unexpected OpenAI API response format");
+    batches
+}
+
+fn format_parts(parts: &[Part]) -> String {
+    parts
+        .iter()
+        .map(|p| format!("### {}\n{}", p.label(), p.text))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn combine_prompt(title: &str, parts: &[Part]) -> String {
+    format!(
+        "Below are summaries of consecutive parts of the video \"{title}\", in order. \
+         Combine them into a single summary of this stretch of the video:\n\n{}",
+        format_parts(parts)
+    )
+}
+
+/// `h:mm:ss` for a time in seconds
+fn clock(seconds: f64) -> String {
+    let secs = seconds as u64;
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Config;
+
+    fn segments(count: usize, secs: f64) -> Vec<Segment> {
+        (0..count)
+            .map(|i| Segment {
+                // 40 characters, so 11 tokens with the separator
+                text: format!("{:040}", i),
+                start: i as f64 * secs,
+                duration: secs,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn chapter(title: &str, start_time: f64) -> Chapter {
+        Chapter {
+            title: title.to_string(),
+            start_time,
+        }
+    }
+
+    fn llm(spec: &str) -> Llm {
+        Llm::resolve(spec, &Config::default()).unwrap()
+    }
 
     #[test]
-    fn test_is_anthropic_model() {
-        assert!(is_anthropic_model("claude-sonnet-4-6"));
-        assert!(is_anthropic_model("claude-3-opus-20240229"));
-        assert!(!is_anthropic_model("gpt-4o"));
-        assert!(!is_anthropic_model("gpt-4o-mini"));
+    fn test_chunk_tokens() {
+        let mut overrides = BTreeMap::new();
+        assert_eq!(chunk_tokens(&llm("claude-sonnet-4-6"), &overrides), 100_000);
+        assert_eq!(chunk_tokens(&llm("gpt-4o-mini"), &overrides), 60_000);
+        assert_eq!(chunk_tokens(&llm("llama3"), &overrides), DEFAULT_CHUNK_TOKENS);
+        assert_eq!(chunk_tokens(&llm("ollama:llama3"), &overrides), OLLAMA_CHUNK_TOKENS);
+
+        overrides.insert("claude".to_string(), 50_000);
+        overrides.insert("claude-haiku".to_string(), 20_000);
+        overrides.insert("ollama:qwen".to_string(), 24_000);
+        assert_eq!(chunk_tokens(&llm("claude-sonnet-4-6"), &overrides), 50_000);
+        assert_eq!(chunk_tokens(&llm("claude-haiku-4-5"), &overrides), 20_000);
+        assert_eq!(chunk_tokens(&llm("ollama:qwen2.5:14b"), &overrides), 24_000);
     }
 
     #[test]
-    fn test_extract_anthropic_text() {
-        let json = serde_json::json!({
-            "content": [
-                {
-                    "type": "text",
-                    "text": "Here is the summary."
-                }
-            ]
-        });
-        assert_eq!(extract_anthropic_text(&json).unwrap(), "Here is the summary.");
+    fn test_split_transcript_by_time() {
+        let parts = split_transcript(&segments(10, 60.0), &[], 44, false);
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].label(), "0:00:00-0:04:00");
+        assert_eq!(parts[2].label(), "0:08:00-0:10:00");
+        assert_eq!(parts[2].text.split(' ').count(), 2);
+
+        let stamped = split_transcript(&segments(10, 60.0), &[], 44, true);
+        assert!(stamped[1].text.starts_with("[04:00] 0000"));
+        assert_eq!(stamped[2].text.lines().count(), 2);
     }
 
     #[test]
-    fn test_extract_anthropic_text_empty() {
-        let json = serde_json::json!({"content": []});
-        assert!(extract_anthropic_text(&json).is_err());
+    fn test_split_transcript_keeps_chapters_whole() {
+        // Chapters of 3, 2 and 5 segments; the first two fit together
+        let chapters = [chapter("Intro", 0.0), chapter("Setup", 180.0), chapter("Demo", 300.0)];
+        let parts = split_transcript(&segments(10, 60.0), &chapters, 60, false);
+        let labels: Vec<String> = parts.iter().map(Part::label).collect();
+        assert_eq!(labels, vec!["0:00:00-0:05:00: Intro, Setup", "0:05:00-0:10:00: Demo"]);
+
+        // A chapter too long for one part is cut between segments
+        let parts = split_transcript(&segments(10, 60.0), &chapters[..1], 44, false);
+        assert_eq!(parts.len(), 3);
+        assert!(parts.iter().all(|p| p.titles == vec!["Intro"]));
     }
 
     #[test]
-    fn test_extract_openai_text() {
-        let json = serde_json::json!({
-            "choices": [
-                {
-                    "message": {
-                        "role": "assistant",
-                        "content": "Summary of the video."
-                    }
-                }
-            ]
-        });
-        assert_eq!(extract_openai_text(&json).unwrap(), "Summary of the video.");
+    fn test_batch_parts() {
+        let part = |text: &str| Part {
+            start: 0.0,
+            end: 0.0,
+            titles: Vec::new(),
+            text: text.to_string(),
+        };
+        let parts = vec![part(&"a".repeat(40)), part(&"b".repeat(40)), part(&"c".repeat(40))];
+        let sizes: Vec<usize> = batch_parts(&parts, 20).iter().map(|b| b.len()).collect();
+        assert_eq!(sizes, vec![2, 1]);
+        // Runs never drop below two, even when two don't fit
+        let sizes: Vec<usize> = batch_parts(&parts, 5).iter().map(|b| b.len()).collect();
+        assert_eq!(sizes, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_needs_split() {
+        let transcript = Transcript {
+            video_id: "long".to_string(),
+            title: "Long".to_string(),
+            language: "en".to_string(),
+            source_language: None,
+            source: crate::TranscriptSource::Caption,
+            segments: segments(10, 60.0),
+            speakers: Default::default(),
+            quality: None,
+        };
+        assert!(needs_split(&transcript, 100));
+        assert!(!needs_split(&transcript, 110));
     }
 
     #[test]
-    fn test_extract_openai_text_empty() {
-        let json = serde_json::json!({"choices": []});
-        assert!(extract_openai_text(&json).is_err());
+    fn test_paid_adds_the_failed_steps_usage() {
+        let usage = |input_tokens, output_tokens| TokenUsage {
+            input_tokens,
+            output_tokens,
+            ..Default::default()
+        };
+        let error = paid(eyre::eyre!("combine failed"), usage(1000, 200));
+        assert_eq!(PaidFailure::<TokenUsage>::spent(&error), Some(&usage(1000, 200)));
+        assert_eq!(error.to_string(), "combine failed");
+
+        let error = paid(error, usage(500, 100));
+        assert_eq!(PaidFailure::<TokenUsage>::spent(&error), Some(&usage(1500, 300)));
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let mut transcript = Transcript {
+            video_id: "abc123".to_string(),
+            title: "Talk".to_string(),
+            language: "en".to_string(),
+            source_language: None,
+            source: crate::TranscriptSource::Caption,
+            segments: segments(3, 10.0),
+            speakers: Default::default(),
+            quality: None,
+        };
+        let (sonnet, haiku) = (llm("claude-sonnet-4-6"), llm("claude-haiku-4-5"));
+        let template = Template::default();
+        let opts = SummaryOptions {
+            llm: &sonnet,
+            template: &template,
+            max_tokens: 100_000,
+            chapters: &[],
+            channel: None,
+            stream: false,
+            cite: false,
+            structured: false,
+        };
+        let key = cache_key(&transcript, &opts);
+        assert_eq!(key.len(), 16);
+        // Streaming changes how the summary arrives, not what it says
+        assert_eq!(cache_key(&transcript, &SummaryOptions { stream: true, ..opts }), key);
+
+        assert_ne!(cache_key(&transcript, &SummaryOptions { llm: &haiku, ..opts }), key);
+        assert_ne!(cache_key(&transcript, &SummaryOptions { cite: true, ..opts }), key);
+        let tldr = Template::builtin("tldr").unwrap();
+        assert_ne!(
+            cache_key(
+                &transcript,
+                &SummaryOptions {
+                    template: &tldr,
+                    ..opts
+                }
+            ),
+            key
+        );
+        transcript.segments[1].text.push_str(" (corrected)");
+        assert_ne!(cache_key(&transcript, &opts), key);
     }
 }
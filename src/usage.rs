@@ -0,0 +1,344 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+use eyre::Result;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+/// What a paid call was for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageKind {
+    Transcription,
+    Llm,
+}
+
+/// One paid API call, as stored in the ledger
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    /// Unix time in seconds
+    pub timestamp: u64,
+    pub user: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    pub kind: UsageKind,
+    pub model: String,
+    pub video_id: String,
+    #[serde(default)]
+    pub audio_minutes: f64,
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    /// Estimated cost in USD
+    pub cost: f64,
+}
+
+impl UsageRecord {
+    /// A record stamped with the current time and user
+    pub fn new(kind: UsageKind, model: &str, video_id: &str, project: Option<&str>) -> Self {
+        Self {
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            user: current_user(),
+            project: project.map(str::to_string),
+            kind,
+            model: model.to_string(),
+            video_id: video_id.to_string(),
+            audio_minutes: 0.0,
+            input_tokens: 0,
+            output_tokens: 0,
+            cost: 0.0,
+        }
+    }
+
+    /// UTC calendar day of the call, as YYYY-MM-DD
+    pub fn day(&self) -> String {
+        let (y, m, d) = civil_from_days((self.timestamp / 86_400) as i64);
+        format!("{y:04}-{m:02}-{d:02}")
+    }
+}
+
+fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// The ledger: one JSON record per line in ~/.local/share/ytx/usage.jsonl
+pub fn ledger_path() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("ytx")
+        .join("usage.jsonl")
+}
+
+/// Append a record to the ledger
+pub fn record(entry: &UsageRecord) -> Result<()> {
+    let path = ledger_path();
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    debug!("Recorded usage: {} {} ${:.4}", entry.model, entry.video_id, entry.cost);
+    Ok(())
+}
+
+/// Read every record in the ledger, skipping lines that don't parse
+pub fn load() -> Result<Vec<UsageRecord>> {
+    let path = ledger_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(parse_ledger(&std::fs::read_to_string(&path)?))
+}
+
+fn parse_ledger(content: &str) -> Vec<UsageRecord> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                debug!("Skipping malformed ledger line: {e}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Field to aggregate usage by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum GroupBy {
+    Day,
+    Model,
+    Video,
+    User,
+    Project,
+}
+
+/// Totals for one group in a usage report
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageRow {
+    pub key: String,
+    pub calls: u64,
+    pub audio_minutes: f64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost: f64,
+}
+
+/// Sum records per group, ordered by key
+pub fn aggregate(records: &[UsageRecord], by: GroupBy) -> Vec<UsageRow> {
+    let mut groups: BTreeMap<String, UsageRow> = BTreeMap::new();
+    for r in records {
+        let key = match by {
+            GroupBy::Day => r.day(),
+            GroupBy::Model => r.model.clone(),
+            GroupBy::Video => r.video_id.clone(),
+            GroupBy::User => r.user.clone(),
+            GroupBy::Project => r.project.clone().unwrap_or_else(|| "-".to_string()),
+        };
+        let row = groups.entry(key.clone()).or_insert_with(|| UsageRow {
+            key,
+            ..Default::default()
+        });
+        row.calls += 1;
+        row.audio_minutes += r.audio_minutes;
+        row.input_tokens += r.input_tokens;
+        row.output_tokens += r.output_tokens;
+        row.cost += r.cost;
+    }
+    groups.into_values().collect()
+}
+
+/// Render rows as an aligned table with a total line
+pub fn render_table(rows: &[UsageRow], by: GroupBy) -> String {
+    let heading = format!("{by:?}").to_uppercase();
+    let width = rows
+        .iter()
+        .map(|r| r.key.len())
+        .max()
+        .unwrap_or(0)
+        .max(heading.len())
+        .max(5);
+
+    let mut out = format!(
+        "{heading:<width$}  {:>6}  {:>10}  {:>12}  {:>12}  {:>10}\n",
+        "CALLS", "AUDIO MIN", "IN TOKENS", "OUT TOKENS", "COST"
+    );
+    let mut total = UsageRow {
+        key: "TOTAL".to_string(),
+        ..Default::default()
+    };
+    for row in rows {
+        out.push_str(&table_line(row, width));
+        total.calls += row.calls;
+        total.audio_minutes += row.audio_minutes;
+        total.input_tokens += row.input_tokens;
+        total.output_tokens += row.output_tokens;
+        total.cost += row.cost;
+    }
+    out.push_str(&table_line(&total, width));
+    out.truncate(out.trim_end().len());
+    out
+}
+
+fn table_line(row: &UsageRow, width: usize) -> String {
+    format!(
+        "{:<width$}  {:>6}  {:>10.1}  {:>12}  {:>12}  {:>10}\n",
+        row.key,
+        row.calls,
+        row.audio_minutes,
+        row.input_tokens,
+        row.output_tokens,
+        format!("${:.4}", row.cost),
+    )
+}
+
+/// Render rows as CSV with a header line
+pub fn render_csv(rows: &[UsageRow], by: GroupBy) -> String {
+    let mut out = format!(
+        "{},calls,audio_minutes,input_tokens,output_tokens,cost_usd\n",
+        format!("{by:?}").to_lowercase()
+    );
+    for row in rows {
+        out.push_str(&format!(
+            "{},{},{:.2},{},{},{:.6}\n",
+            csv_field(&row.key),
+            row.calls,
+            row.audio_minutes,
+            row.input_tokens,
+            row.output_tokens,
+            row.cost
+        ));
+    }
+    out.truncate(out.trim_end().len());
+    out
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Render rows as a JSON array
+pub fn render_json(rows: &[UsageRow]) -> String {
+    serde_json::to_string_pretty(rows).unwrap_or_default()
+}
+
+/// Validate a YYYY-MM-DD day, as accepted by `ytx usage --since`
+pub fn parse_day(input: &str) -> Result<String> {
+    let valid = input.len() == 10
+        && input.char_indices().all(|(i, c)| match i {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        });
+    if !valid {
+        eyre::bail!("invalid day '{input}', expected YYYY-MM-DD");
+    }
+    Ok(input.to_string())
+}
+
+/// Days since 1970-01-01 to a (year, month, day) civil date
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    (y, m, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(timestamp: u64, model: &str, video_id: &str, cost: f64) -> UsageRecord {
+        UsageRecord {
+            timestamp,
+            user: "scott".to_string(),
+            project: None,
+            kind: UsageKind::Llm,
+            model: model.to_string(),
+            video_id: video_id.to_string(),
+            audio_minutes: 0.0,
+            input_tokens: 1000,
+            output_tokens: 200,
+            cost,
+        }
+    }
+
+    #[test]
+    fn test_day() {
+        assert_eq!(record(0, "m", "v", 0.0).day(), "1970-01-01");
+        assert_eq!(record(951_782_400, "m", "v", 0.0).day(), "2000-02-29");
+        assert_eq!(record(1_771_459_200, "m", "v", 0.0).day(), "2026-02-19");
+    }
+
+    #[test]
+    fn test_parse_day() {
+        assert_eq!(parse_day("2026-02-19").unwrap(), "2026-02-19");
+        assert!(parse_day("2026-2-19").is_err());
+        assert!(parse_day("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_parse_ledger_skips_bad_lines() {
+        let line = serde_json::to_string(&record(0, "gpt-4o", "abc", 0.01)).unwrap();
+        let content = format!("{line}\nnot json\n\n{line}\n");
+        assert_eq!(parse_ledger(&content).len(), 2);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let records = vec![
+            record(1_771_459_200, "claude-sonnet-4-6", "a", 0.10),
+            record(1_771_459_300, "whisper-1", "a", 0.30),
+            record(1_771_545_600, "claude-sonnet-4-6", "b", 0.05),
+        ];
+
+        let by_model = aggregate(&records, GroupBy::Model);
+        assert_eq!(by_model.len(), 2);
+        assert_eq!(by_model[0].key, "claude-sonnet-4-6");
+        assert_eq!(by_model[0].calls, 2);
+        assert!((by_model[0].cost - 0.15).abs() < 1e-9);
+        assert_eq!(by_model[0].input_tokens, 2000);
+
+        let by_day = aggregate(&records, GroupBy::Day);
+        assert_eq!(
+            by_day.iter().map(|r| r.key.as_str()).collect::<Vec<_>>(),
+            vec!["2026-02-19", "2026-02-20"]
+        );
+
+        let by_project = aggregate(&records, GroupBy::Project);
+        assert_eq!(by_project[0].key, "-");
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let rows = aggregate(&[record(0, "a,b", "v", 0.5)], GroupBy::Model);
+        assert_eq!(
+            render_csv(&rows, GroupBy::Model),
+            "model,calls,audio_minutes,input_tokens,output_tokens,cost_usd\n\"a,b\",1,0.00,1000,200,0.500000"
+        );
+    }
+
+    #[test]
+    fn test_render_table_has_total() {
+        let rows = aggregate(&[record(0, "m", "v", 0.5), record(0, "n", "v", 0.25)], GroupBy::Model);
+        let table = render_table(&rows, GroupBy::Model);
+        let last = table.lines().last().unwrap();
+        assert!(last.starts_with("TOTAL"));
+        assert!(last.ends_with("$0.7500"));
+    }
+}
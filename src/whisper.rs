@@ -1,29 +1,85 @@
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
 
 use eyre::{Result, bail};
 use log::debug;
 use reqwest::multipart;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 
-use crate::{Segment, Transcript, TranscriptSource};
+use crate::workspace::{self, Workspace};
+use crate::{AUTO_LANG, Chapter, Segment, Transcript, TranscriptSource};
+
+mod job;
+mod language;
+mod local;
+mod mp3;
+mod preprocess;
+mod quality;
+mod timing;
+
+use job::Job;
+pub use local::transcribe_local;
+use mp3::Mp3;
+use preprocess::{OffsetMap, Opus};
+pub use preprocess::{Preprocess, parse_speed};
+use quality::{SegmentStats, Tally, Verdict};
+
+/// Attempts per upload before a transcription request is given up on
+const UPLOAD_ATTEMPTS: u32 = 3;
+
+/// Whisper only looks at the last 224 tokens of a prompt; this keeps us near that
+const MAX_PROMPT_CHARS: usize = 800;
+
+/// How much of the previous chunk's text is carried into the next chunk's prompt
+const CONTEXT_TAIL_CHARS: usize = 300;
+
+/// Language recorded when auto-detection was asked for but none was reported
+const UNDETERMINED_LANG: &str = "und";
+
+/// Upper bound on keywords pulled from a video description
+const MAX_DESCRIPTION_KEYWORDS: usize = 40;
 
 /// Maximum file size for a single Whisper API upload (25 MB)
 const MAX_UPLOAD_BYTES: u64 = 25 * 1024 * 1024;
 
+/// Size each chunk aims for, leaving headroom under the upload limit
+const CHUNK_TARGET_BYTES: u64 = MAX_UPLOAD_BYTES * 9 / 10;
+
+/// Audio shared between neighbouring chunks on each side of a cut
+const CHUNK_OVERLAP_SECS: f64 = 2.0;
+
+/// How far before a size-based cut point to look for a silence
+const SILENCE_SEARCH_WINDOW_SECS: f64 = 90.0;
+
+/// Level and minimum length that count as silence for cut placement
+const SILENCE_NOISE_DB: i32 = -35;
+const SILENCE_MIN_SECS: f64 = 0.4;
+
+/// Bounds on how many words may be de-duplicated when stitching chunks
+const MIN_OVERLAP_WORDS: usize = 2;
+const MAX_OVERLAP_WORDS: usize = 12;
+
 /// Whisper transcription model
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub enum WhisperModel {
     Gpt4oMiniTranscribe,
     Gpt4oTranscribe,
+    /// Labels segments by speaker. Labels are assigned per upload, so on
+    /// chunked files the same label may not mean the same person throughout.
+    Gpt4oTranscribeDiarize,
     #[default]
     Whisper1,
 }
 
 impl WhisperModel {
-    fn api_name(&self) -> &str {
+    pub fn api_name(&self) -> &str {
         match self {
             WhisperModel::Gpt4oMiniTranscribe => "gpt-4o-mini-transcribe",
             WhisperModel::Gpt4oTranscribe => "gpt-4o-transcribe",
+            WhisperModel::Gpt4oTranscribeDiarize => "gpt-4o-transcribe-diarize",
             WhisperModel::Whisper1 => "whisper-1",
         }
     }
@@ -31,6 +87,7 @@ impl WhisperModel {
     fn response_format(&self) -> &str {
         match self {
             WhisperModel::Whisper1 => "verbose_json",
+            WhisperModel::Gpt4oTranscribeDiarize => "diarized_json",
             // Newer transcribe models only support "json" or "text"
             _ => "json",
         }
@@ -39,56 +96,214 @@ impl WhisperModel {
     fn supports_timestamp_granularities(&self) -> bool {
         matches!(self, WhisperModel::Whisper1)
     }
+
+    fn supports_prompt(&self) -> bool {
+        !matches!(self, WhisperModel::Gpt4oTranscribeDiarize)
+    }
+
+    /// The diarizing model requires a chunking strategy for inputs over 30s
+    fn requires_chunking_strategy(&self) -> bool {
+        matches!(self, WhisperModel::Gpt4oTranscribeDiarize)
+    }
+}
+
+impl std::str::FromStr for WhisperModel {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        [
+            WhisperModel::Gpt4oMiniTranscribe,
+            WhisperModel::Gpt4oTranscribe,
+            WhisperModel::Gpt4oTranscribeDiarize,
+            WhisperModel::Whisper1,
+        ]
+        .into_iter()
+        .find(|m| m.api_name() == s)
+        .ok_or_else(|| {
+            eyre::eyre!(
+                "unknown transcription model '{s}' (expected gpt-4o-mini-transcribe, gpt-4o-transcribe, \
+                 gpt-4o-transcribe-diarize or whisper-1)"
+            )
+        })
+    }
+}
+
+/// Settings for a Whisper transcription run
+#[derive(Debug, Clone)]
+pub struct WhisperOptions {
+    pub model: WhisperModel,
+    /// Spoken language, or [`AUTO_LANG`] to have Whisper detect it
+    pub lang: String,
+    /// Translate the speech into English instead of transcribing it as spoken
+    pub translate: bool,
+    /// Maximum number of chunks uploaded concurrently. With 1, each chunk is
+    /// also prompted with the end of the previous chunk's text; with more,
+    /// chunks are uploaded before the text before them is known.
+    pub jobs: usize,
+    /// Directory under which each run's scratch workspace is created
+    pub temp_root: PathBuf,
+    /// Retain the downloaded audio under the cache directory for reuse
+    pub keep_audio: bool,
+    /// Proper nouns and jargon to bias recognition towards
+    pub glossary: Vec<String>,
+    /// Silence trimming and speed-up applied before upload
+    pub preprocess: Preprocess,
+}
+
+impl Default for WhisperOptions {
+    fn default() -> Self {
+        Self {
+            model: WhisperModel::default(),
+            lang: "en".to_string(),
+            translate: false,
+            jobs: 1,
+            temp_root: workspace::default_root(),
+            keep_audio: false,
+            glossary: Vec::new(),
+            preprocess: Preprocess::default(),
+        }
+    }
 }
 
-/// Transcribe a video using yt-dlp + Whisper API
+/// Transcribe a video using yt-dlp + Whisper API.
+///
+/// `metadata` (from [`fetch_metadata`]) supplies the title and the terms used
+/// to prompt Whisper; callers fetch it first to estimate cost. With
+/// `opts.translate` the transcript is an English translation, and records the
+/// spoken language as its `source_language`.
+///
+/// Chunked transcriptions also return the seconds of audio this run
+/// uploaded, which is less than the whole when an earlier run's chunks were
+/// reused. If a chunk fails, the error is a [`crate::PaidFailure`] with the
+/// seconds uploaded before it.
 pub async fn transcribe(
     client: &reqwest::Client,
     video_id: &str,
-    lang: &str,
-    model: &WhisperModel,
-) -> Result<Transcript> {
+    metadata: &VideoMetadata,
+    opts: &WhisperOptions,
+) -> Result<(Transcript, Option<f64>)> {
     let api_key = std::env::var("OPENAI_API_KEY")
         .map_err(|_| eyre::eyre!("OPENAI_API_KEY environment variable not set (required for Whisper fallback)"))?;
 
-    // Download audio via yt-dlp
-    let audio_path = download_audio(video_id)?;
+    if opts.translate && opts.model != WhisperModel::Whisper1 {
+        bail!(
+            "translation is only supported by whisper-1, not {}",
+            opts.model.api_name()
+        );
+    }
+
+    // All intermediate files live here and are removed when it drops,
+    // on success or error alike
+    let workspace = Workspace::create(&opts.temp_root)?;
 
-    // Get video title from yt-dlp
-    let title = get_video_title(video_id).unwrap_or_default();
+    let audio_path = source_audio(video_id, &workspace, opts)?;
+
+    // Prime Whisper with the names and terms it is likely to hear
+    let prompt = build_prompt(metadata, &opts.glossary);
+    debug!("Whisper prompt: {prompt}");
+
+    // Fewer minutes uploaded is less to pay for; timings are mapped back after
+    let (upload_path, offsets) = if opts.preprocess.is_enabled() {
+        let (path, offsets) = preprocess::prepare(&audio_path, &workspace, &opts.preprocess)?;
+        (path, Some(offsets))
+    } else {
+        (audio_path.clone(), None)
+    };
+    let audio = Audio {
+        source: audio_path,
+        upload: upload_path,
+        offsets,
+    };
+    let upload_path = &audio.upload;
 
     // Check file size and chunk if needed
-    let file_size = std::fs::metadata(&audio_path)?.len();
+    let file_size = std::fs::metadata(upload_path)?.len();
     debug!("Audio file size: {file_size} bytes");
 
-    let segments = if file_size > MAX_UPLOAD_BYTES {
-        transcribe_chunked(client, &api_key, &audio_path, model, lang).await?
+    // Uploads are retried individually, so a failure never re-sends audio
+    // that was already transcribed
+    let (mut transcription, uploaded_secs) = if file_size > MAX_UPLOAD_BYTES {
+        let (transcription, secs) =
+            transcribe_chunked(client, &api_key, video_id, &audio, &workspace, &prompt, opts).await?;
+        (transcription, Some(secs))
     } else {
-        transcribe_file(client, &api_key, &audio_path, model, lang).await?
+        let transcription = crate::retry(UPLOAD_ATTEMPTS, || {
+            transcribe_file(client, &api_key, upload_path, opts, &prompt)
+        })
+        .await?;
+        (transcription, None)
     };
 
-    Ok(Transcript {
+    if timing::is_untimed(&transcription.segments) {
+        let duration = ChunkSource::open(upload_path)?.duration();
+        estimate_timings(&mut transcription, upload_path, duration);
+    }
+
+    if let Some(offsets) = &audio.offsets {
+        offsets.remap(&mut transcription.segments);
+    }
+
+    let mut tally = transcription.quality;
+    tally.collapsed += quality::collapse_repeated_segments(&mut transcription.segments);
+    let quality = tally.quality();
+    debug!("Transcript quality: {quality:?}");
+
+    let (language, source_language) = languages(opts, metadata, transcription.language.as_deref());
+    let transcript = Transcript {
         video_id: video_id.to_string(),
-        title,
-        language: lang.to_string(),
+        title: metadata.title.clone(),
+        language,
+        source_language,
         source: TranscriptSource::Whisper,
-        segments,
-    })
+        segments: transcription.segments,
+        speakers: Default::default(),
+        quality: Some(quality),
+    };
+    Ok((transcript, uploaded_secs))
 }
 
-fn download_audio(video_id: &str) -> Result<PathBuf> {
-    let url = format!("https://www.youtube.com/watch?v={video_id}");
-    let output_template = format!("/tmp/ytx-{video_id}.%(ext)s");
-    let output_path = PathBuf::from(format!("/tmp/ytx-{video_id}.mp3"));
-
-    // Reuse existing file on retry (avoid re-downloading after API errors)
-    if output_path.exists() {
-        debug!(
-            "Audio file already exists, skipping download: {}",
-            output_path.display()
-        );
-        return Ok(output_path);
+/// A video's audio: downloaded via yt-dlp, or reused from `--keep-audio`
+fn source_audio(video_id: &str, workspace: &Workspace, opts: &WhisperOptions) -> Result<PathBuf> {
+    let audio_path = match kept_audio_path(video_id).filter(|p| p.exists()) {
+        Some(kept) => {
+            debug!("Reusing kept audio: {}", kept.display());
+            kept
+        }
+        None => download_audio(video_id, workspace)?,
+    };
+    if opts.keep_audio {
+        keep_audio(video_id, &audio_path)?;
     }
+    Ok(audio_path)
+}
+
+/// The transcript's language and, for a translation, the spoken one
+fn languages(opts: &WhisperOptions, metadata: &VideoMetadata, detected: Option<&str>) -> (String, Option<String>) {
+    let spoken = if opts.lang != AUTO_LANG {
+        opts.lang.clone()
+    } else if opts.translate {
+        // Translations report the output language, so rely on YouTube's
+        metadata
+            .language
+            .clone()
+            .unwrap_or_else(|| UNDETERMINED_LANG.to_string())
+    } else {
+        let detected = detected.unwrap_or(UNDETERMINED_LANG);
+        debug!("Detected language: {detected}");
+        detected.to_string()
+    };
+
+    if opts.translate {
+        ("en".to_string(), Some(spoken))
+    } else {
+        (spoken, None)
+    }
+}
+
+fn download_audio(video_id: &str, workspace: &Workspace) -> Result<PathBuf> {
+    let url = format!("https://www.youtube.com/watch?v={video_id}");
+    let output_template = workspace.file("audio.%(ext)s").to_string_lossy().to_string();
+    let output_path = workspace.file("audio.mp3");
 
     debug!("Downloading audio via yt-dlp: {url}");
 
@@ -128,44 +343,207 @@ fn download_audio(video_id: &str) -> Result<PathBuf> {
     Ok(output_path)
 }
 
-fn get_video_title(video_id: &str) -> Option<String> {
+/// Where `--keep-audio` retains a video's audio between runs
+pub fn kept_audio_path(video_id: &str) -> Option<PathBuf> {
+    dirs::cache_dir().map(|d| d.join("ytx").join("audio").join(format!("{video_id}.mp3")))
+}
+
+fn keep_audio(video_id: &str, audio_path: &Path) -> Result<()> {
+    let Some(kept) = kept_audio_path(video_id) else {
+        bail!("no cache directory available to keep audio in");
+    };
+    if kept != audio_path {
+        std::fs::create_dir_all(kept.parent().unwrap())?;
+        std::fs::copy(audio_path, &kept)?;
+        debug!("Kept audio: {}", kept.display());
+    }
+    Ok(())
+}
+
+/// Video details from yt-dlp
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct VideoMetadata {
+    pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    /// Length in seconds
+    pub duration: Option<f64>,
+    /// Spoken language as declared by the uploader, if any
+    pub language: Option<String>,
+    /// Name of the channel that posted the video
+    pub channel: Option<String>,
+    /// Uploader-marked sections, which yt-dlp reports as null when there are none
+    pub chapters: Option<Vec<Chapter>>,
+}
+
+/// Look up a video's details without downloading it
+pub fn fetch_metadata(video_id: &str) -> Result<VideoMetadata> {
     let url = format!("https://www.youtube.com/watch?v={video_id}");
-    Command::new("yt-dlp")
-        .args(["--get-title", "--no-playlist", &url])
-        .output()
-        .ok()
-        .filter(|o| o.status.success())
-        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+    let output = Command::new("yt-dlp")
+        .args(["--dump-single-json", "--no-playlist", "--skip-download", &url])
+        .output()?;
+
+    if !output.status.success() {
+        bail!("yt-dlp exited with status {}", output.status);
+    }
+
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+/// Build the transcription prompt: glossary terms first (they matter most),
+/// then tags and likely proper nouns from the description, then the title.
+fn build_prompt(metadata: &VideoMetadata, glossary: &[String]) -> String {
+    let mut terms: Vec<String> = Vec::new();
+    let candidates = glossary
+        .iter()
+        .cloned()
+        .chain(metadata.tags.iter().cloned())
+        .chain(description_keywords(&metadata.description));
+    for term in candidates {
+        if !terms.iter().any(|t| t.eq_ignore_ascii_case(&term)) {
+            terms.push(term);
+        }
+    }
+
+    let mut prompt = metadata.title.trim().to_string();
+    if !prompt.is_empty() && !prompt.ends_with(['.', '!', '?']) {
+        prompt.push('.');
+    }
+
+    for term in terms {
+        let sep = if prompt.is_empty() { "" } else { " " };
+        let piece = format!("{sep}{term},");
+        if prompt.len() + piece.len() > MAX_PROMPT_CHARS - CONTEXT_TAIL_CHARS {
+            break;
+        }
+        prompt.push_str(&piece);
+    }
+
+    prompt.trim_end_matches(',').to_string()
+}
+
+/// Hashtags and capitalised or mixed-case words that aren't just the start of
+/// a sentence, which are likely names, products or jargon
+fn description_keywords(description: &str) -> Vec<String> {
+    let mut keywords: Vec<String> = Vec::new();
+    let mut sentence_start = true;
+
+    for raw in description.split_whitespace() {
+        if raw.contains("://") {
+            continue;
+        }
+
+        let word = raw.trim_matches(|c: char| !c.is_alphanumeric() && c != '#');
+        let is_hashtag = word.starts_with('#');
+        let word = word.trim_start_matches('#');
+
+        let has_upper_after_first = word.chars().skip(1).any(|c| c.is_uppercase());
+        let capitalised = word.chars().next().is_some_and(|c| c.is_uppercase());
+        let keep = word.chars().count() >= 3
+            && word.chars().any(|c| c.is_alphabetic())
+            && (is_hashtag || has_upper_after_first || (capitalised && !sentence_start));
+
+        if keep && !keywords.iter().any(|k| k.eq_ignore_ascii_case(word)) {
+            keywords.push(word.to_string());
+            if keywords.len() == MAX_DESCRIPTION_KEYWORDS {
+                break;
+            }
+        }
+
+        sentence_start = raw.ends_with(['.', '!', '?', ':']);
+    }
+
+    keywords
+}
+
+/// Prompt for one chunk: the base prompt followed by the tail of the previous
+/// chunk's text, which Whisper treats as the preceding context
+fn chunk_prompt(base: &str, previous: Option<&[Segment]>) -> String {
+    let Some(previous) = previous else {
+        return base.to_string();
+    };
+
+    let text = previous.iter().map(|s| s.text.as_str()).collect::<Vec<_>>().join(" ");
+    let mut cut = text.len().saturating_sub(CONTEXT_TAIL_CHARS);
+    while !text.is_char_boundary(cut) {
+        cut += 1;
+    }
+    // Start the tail on a word boundary
+    let tail = match text[cut..].find(' ') {
+        Some(i) if cut > 0 => &text[cut + i + 1..],
+        _ => &text[cut..],
+    };
+
+    if base.is_empty() {
+        tail.to_string()
+    } else {
+        format!("{base} {tail}")
+    }
+}
+
+/// Segments from one upload, with the language Whisper detected in it (only
+/// reported by verbose_json) and the hallucination checks made on it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Transcription {
+    segments: Vec<Segment>,
+    #[serde(default)]
+    language: Option<String>,
+    #[serde(default)]
+    quality: Tally,
 }
 
 async fn transcribe_file(
     client: &reqwest::Client,
     api_key: &str,
     audio_path: &Path,
-    model: &WhisperModel,
-    lang: &str,
-) -> Result<Vec<Segment>> {
+    opts: &WhisperOptions,
+    prompt: &str,
+) -> Result<Transcription> {
     debug!("Uploading {} to Whisper API", audio_path.display());
+    let model = &opts.model;
 
     let file_bytes = std::fs::read(audio_path)?;
     let file_name = audio_path.file_name().unwrap_or_default().to_string_lossy().to_string();
 
-    let file_part = multipart::Part::bytes(file_bytes)
-        .file_name(file_name)
-        .mime_str("audio/mpeg")?;
+    let mime = match audio_path.extension().and_then(|e| e.to_str()) {
+        Some("ogg") => "audio/ogg",
+        _ => "audio/mpeg",
+    };
+    let file_part = multipart::Part::bytes(file_bytes).file_name(file_name).mime_str(mime)?;
 
     let mut form = multipart::Form::new()
         .part("file", file_part)
         .text("model", model.api_name().to_string())
-        .text("language", lang.to_string())
         .text("response_format", model.response_format().to_string());
 
-    if model.supports_timestamp_granularities() {
-        form = form.text("timestamp_granularities[]", "segment");
+    // Translations always detect the language and return segment timestamps
+    // in verbose_json, and take none of these options
+    if !opts.translate {
+        // Leaving the language out makes Whisper detect it
+        if opts.lang != AUTO_LANG {
+            form = form.text("language", opts.lang.clone());
+        }
+
+        if model.supports_timestamp_granularities() {
+            form = form.text("timestamp_granularities[]", "segment");
+        }
+
+        if model.requires_chunking_strategy() {
+            form = form.text("chunking_strategy", "auto");
+        }
+    }
+
+    if !prompt.is_empty() && model.supports_prompt() {
+        form = form.text("prompt", prompt.to_string());
     }
 
     let resp = client
-        .post("https://api.openai.com/v1/audio/transcriptions")
+        .post(if opts.translate {
+            "https://api.openai.com/v1/audio/translations"
+        } else {
+            "https://api.openai.com/v1/audio/transcriptions"
+        })
         .bearer_auth(api_key)
         .multipart(form)
         .send()
@@ -181,10 +559,62 @@ async fn transcribe_file(
     parse_whisper_response(&json)
 }
 
-fn parse_whisper_response(json: &serde_json::Value) -> Result<Vec<Segment>> {
-    // verbose_json format has a "segments" array
+/// Replace a plain-text result with sentence segments whose timings are
+/// estimated over the `duration` seconds of `audio_path`
+fn estimate_timings(transcription: &mut Transcription, audio_path: &Path, duration: f64) {
+    if !timing::is_untimed(&transcription.segments) {
+        return;
+    }
+    let silences = detect_silences(audio_path).unwrap_or_else(|e| {
+        debug!("Silence detection unavailable, spreading timings by length only: {e}");
+        Vec::new()
+    });
+    let text = transcription
+        .segments
+        .iter()
+        .map(|s| s.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    transcription.segments = timing::synthesize_segments(&text, duration, &silences);
+    debug!(
+        "Estimated timings for {} segments over {duration:.1}s",
+        transcription.segments.len()
+    );
+}
+
+/// Language reported in a response, as an ISO code where it can be mapped
+fn parse_detected_language(json: &serde_json::Value) -> Option<String> {
+    let name = json.get("language")?.as_str()?.trim();
+    if name.is_empty() {
+        return None;
+    }
+    Some(language::iso_code(name).map_or_else(|| name.to_lowercase(), str::to_string))
+}
+
+/// The language most chunks were detected as
+fn majority_language<'a>(languages: impl Iterator<Item = &'a str>) -> Option<String> {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for lang in languages {
+        match counts.iter_mut().find(|(l, _)| *l == lang) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((lang, 1)),
+        }
+    }
+    // max_by_key keeps the last of equal counts; reverse so the earliest wins ties
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|&(_, n)| n)
+        .map(|(l, _)| l.to_string())
+}
+
+fn parse_whisper_response(json: &serde_json::Value) -> Result<Transcription> {
+    let language = parse_detected_language(json);
+    let mut tally = Tally::default();
+
+    // verbose_json and diarized_json formats have a "segments" array
     if let Some(segments) = json.get("segments").and_then(|s| s.as_array()) {
-        return Ok(segments
+        let segments = segments
             .iter()
             .filter_map(|seg| {
                 let text = seg.get("text")?.as_str()?.trim().to_string();
@@ -193,87 +623,425 @@ fn parse_whisper_response(json: &serde_json::Value) -> Result<Vec<Segment>> {
                 if text.is_empty() {
                     return None;
                 }
+
+                let stats = SegmentStats::from_json(seg);
+                let verdict = quality::assess(&text, &stats);
+                tally.record(verdict, &stats);
+                if verdict == Verdict::Drop {
+                    debug!("Dropping likely hallucination at {start:.1}s: {text}");
+                    return None;
+                }
+
+                let speaker = seg.get("speaker").and_then(|s| s.as_str()).map(str::to_string);
                 Some(Segment {
-                    text,
+                    text: collapse_text_loops(text, &mut tally),
                     start,
                     duration: end - start,
+                    speaker,
+                    suspect: verdict == Verdict::Flag,
+                    ..Default::default()
                 })
             })
-            .collect());
+            .collect();
+        return Ok(Transcription {
+            segments,
+            language,
+            quality: tally,
+        });
     }
 
     // Fallback: plain text response
     if let Some(text) = json.get("text").and_then(|t| t.as_str()) {
-        return Ok(vec![Segment {
-            text: text.trim().to_string(),
-            start: 0.0,
-            duration: 0.0,
-        }]);
+        let text = text.trim().to_string();
+        let verdict = quality::assess(&text, &SegmentStats::default());
+        tally.record(verdict, &SegmentStats::default());
+        return Ok(Transcription {
+            segments: vec![Segment {
+                text: collapse_text_loops(text, &mut tally),
+                suspect: verdict == Verdict::Flag,
+                ..Default::default()
+            }],
+            language,
+            quality: tally,
+        });
     }
 
     bail!("unexpected Whisper API response format");
 }
 
+fn collapse_text_loops(text: String, tally: &mut Tally) -> String {
+    match quality::collapse_loops(&text) {
+        Some(collapsed) => {
+            debug!("Collapsed a repetition loop: {text}");
+            tally.collapsed += 1;
+            collapsed
+        }
+        None => text,
+    }
+}
+
+/// Downloaded audio and what's uploaded of it
+struct Audio {
+    source: PathBuf,
+    /// The source, or a preprocessed copy of it
+    upload: PathBuf,
+    /// Maps preprocessed times back to the source's
+    offsets: Option<OffsetMap>,
+}
+
+/// Audio being split into chunks for upload
+enum ChunkSource {
+    /// Cut on frame boundaries, without re-encoding
+    Mp3(Mp3),
+    /// Preprocessed audio, cut by ffmpeg
+    Opus(Opus),
+}
+
+impl ChunkSource {
+    fn open(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("ogg") => Ok(Self::Opus(Opus::open(path)?)),
+            _ => {
+                let mp3 = Mp3::open(path)?;
+                debug!("Indexed {} MP3 frames", mp3.frames().len());
+                Ok(Self::Mp3(mp3))
+            }
+        }
+    }
+
+    fn extension(&self) -> &str {
+        match self {
+            Self::Mp3(_) => "mp3",
+            Self::Opus(_) => "ogg",
+        }
+    }
+
+    fn duration(&self) -> f64 {
+        match self {
+            Self::Mp3(mp3) => mp3.duration(),
+            Self::Opus(opus) => opus.duration(),
+        }
+    }
+
+    fn reach(&self, start: f64, max_bytes: u64) -> f64 {
+        match self {
+            Self::Mp3(mp3) => mp3.reach(start, max_bytes),
+            Self::Opus(opus) => opus.reach(start, max_bytes),
+        }
+    }
+
+    /// Write `start..end` to `path`, returning the time the written audio starts at
+    fn write_range(&self, start: f64, end: f64, path: &Path) -> Result<f64> {
+        match self {
+            Self::Mp3(mp3) => mp3.write_range(start, end, path),
+            Self::Opus(opus) => opus.write_range(start, end, path),
+        }
+    }
+}
+
+/// Transcribe a file too large for one upload, in overlapping chunks.
+///
+/// Each finished chunk is checkpointed, so a failed or interrupted run picks
+/// up where it left off instead of paying for the same audio again. Returns
+/// the transcription with the seconds of audio uploaded by this run.
 async fn transcribe_chunked(
     client: &reqwest::Client,
     api_key: &str,
-    audio_path: &Path,
-    model: &WhisperModel,
-    lang: &str,
-) -> Result<Vec<Segment>> {
-    // Split audio into chunks using yt-dlp's time ranges
-    // Each chunk is ~20 minutes to stay under 25MB at 64kbps
-    let chunk_duration_secs = 1200; // 20 minutes
-    let file_size = std::fs::metadata(audio_path)?.len();
-    let estimated_duration = file_size as f64 / (64_000.0 / 8.0); // 64kbps
-    let num_chunks = (estimated_duration / chunk_duration_secs as f64).ceil() as usize;
-
-    debug!("Splitting into {num_chunks} chunks of {chunk_duration_secs}s each");
+    video_id: &str,
+    audio: &Audio,
+    workspace: &Workspace,
+    prompt: &str,
+    opts: &WhisperOptions,
+) -> Result<(Transcription, f64)> {
+    let audio_path = audio.upload.as_path();
+    let source = ChunkSource::open(audio_path)?;
+    let duration = source.duration();
+    // Preprocessed audio isn't byte-identical from run to run, so its job is
+    // keyed on the download and how it was processed
+    let audio_sha256 = match (&audio.offsets, &source) {
+        (Some(offsets), _) => offsets.job_checksum(&std::fs::read(&audio.source)?, &opts.preprocess),
+        (None, ChunkSource::Mp3(mp3)) => job::checksum(mp3.bytes()),
+        (None, ChunkSource::Opus(_)) => job::checksum(&std::fs::read(audio_path)?),
+    };
+
+    let job = Job::resume_or_create(
+        video_id,
+        &audio_sha256,
+        opts.model.api_name(),
+        &opts.lang,
+        opts.translate,
+        || {
+            let silences = detect_silences(audio_path).unwrap_or_else(|e| {
+                debug!("Silence detection unavailable, cutting at size limits: {e}");
+                Vec::new()
+            });
+            debug!("Audio duration: {duration:.1}s, {} silences detected", silences.len());
+            plan_chunks(duration, &silences, |start| source.reach(start, CHUNK_TARGET_BYTES))
+        },
+    )?;
+    let chunks = job.chunks().to_vec();
+
+    let mut results: Vec<Option<Transcription>> = chunks.iter().map(|c| job.completed(c.index)).collect();
+    let done = results.iter().filter(|r| r.is_some()).count();
+    debug!(
+        "{} chunks ({done} already transcribed), uploading {} at a time",
+        chunks.len(),
+        opts.jobs
+    );
+
+    // Uploading one at a time lets every chunk see the text before it
+    let sequential = opts.jobs <= 1;
+    let semaphore = Arc::new(Semaphore::new(opts.jobs.max(1)));
+    let mut uploads = JoinSet::new();
+    let mut uploaded_secs = 0.0;
+
+    // Chunks uploaded before a failure are paid for, so the error carries them
+    let uploaded = async {
+        for chunk in &chunks {
+            if results[chunk.index].is_some() {
+                continue;
+            }
+            let chunk_path = workspace.file(&format!("chunk-{}.{}", chunk.index, source.extension()));
+            let chunk_start = source.write_range(chunk.start, chunk.end, &chunk_path)?;
+            let chunk_duration = chunk.end - chunk_start;
+
+            let previous = chunk
+                .index
+                .checked_sub(1)
+                .and_then(|i| results[i].as_ref())
+                .map(|t| t.segments.as_slice());
+            let prompt = chunk_prompt(prompt, previous);
+
+            let semaphore = Arc::clone(&semaphore);
+            let client = client.clone();
+            let api_key = api_key.to_string();
+            let opts = opts.clone();
+            let index = chunk.index;
+
+            uploads.spawn(async move {
+                let _permit = semaphore.acquire_owned().await?;
+                debug!("Uploading chunk {index}");
+                let result = crate::retry(UPLOAD_ATTEMPTS, || {
+                    transcribe_file(&client, &api_key, &chunk_path, &opts, &prompt)
+                })
+                .await
+                .map(|mut transcription| {
+                    estimate_timings(&mut transcription, &chunk_path, chunk_duration);
+                    transcription
+                });
+
+                // Clean up chunk
+                let _ = std::fs::remove_file(&chunk_path);
+
+                let mut transcription = result.map_err(|e| eyre::eyre!("chunk {index} failed: {e}"))?;
+
+                // Shift timestamps from chunk-relative to file-relative
+                for seg in &mut transcription.segments {
+                    seg.start += chunk_start;
+                }
+                Ok::<_, eyre::Report>((index, transcription, chunk_duration))
+            });
+
+            if sequential && let Some(joined) = uploads.join_next().await {
+                let (index, transcription, secs) = joined??;
+                record_chunk(&job, &mut results, index, transcription);
+                uploaded_secs += secs;
+            }
+        }
+
+        while let Some(joined) = uploads.join_next().await {
+            // Returning early drops the JoinSet, which aborts the remaining uploads;
+            // chunks finished so far are already checkpointed
+            let (index, transcription, secs) = joined??;
+            record_chunk(&job, &mut results, index, transcription);
+            uploaded_secs += secs;
+        }
+        Ok::<_, eyre::Report>(())
+    }
+    .await;
+    if let Err(e) = uploaded {
+        return Err(crate::PaidFailure::wrap(e, uploaded_secs));
+    }
+
+    let language = majority_language(results.iter().flatten().filter_map(|t| t.language.as_deref()));
+    let mut tally = Tally::default();
+    for transcription in results.iter().flatten() {
+        tally.merge(&transcription.quality);
+    }
 
     let mut all_segments = Vec::new();
-    let mut time_offset = 0.0_f64;
-
-    for i in 0..num_chunks {
-        let start_time = i as f64 * chunk_duration_secs as f64;
-        let chunk_path = PathBuf::from(format!("/tmp/ytx-chunk-{i}.mp3"));
-
-        // Use ffmpeg to split (yt-dlp doesn't support time ranges on local files)
-        let status = Command::new("ffmpeg")
-            .args([
-                "-y",
-                "-i",
-                &audio_path.to_string_lossy(),
-                "-ss",
-                &format!("{start_time}"),
-                "-t",
-                &format!("{chunk_duration_secs}"),
-                "-acodec",
-                "copy",
-                &chunk_path.to_string_lossy(),
-            ])
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()?;
-
-        if !status.success() {
-            bail!("ffmpeg failed to split audio at offset {start_time}s");
+    for (chunk, transcription) in chunks.iter().zip(results) {
+        stitch_segments(&mut all_segments, transcription.unwrap_or_default().segments, chunk);
+    }
+
+    job.finish();
+    let transcription = Transcription {
+        segments: all_segments,
+        language,
+        quality: tally,
+    };
+    Ok((transcription, uploaded_secs))
+}
+
+fn record_chunk(job: &Job, results: &mut [Option<Transcription>], index: usize, transcription: Transcription) {
+    if let Err(e) = job.checkpoint(index, &transcription) {
+        debug!("Failed to checkpoint chunk {index}: {e}");
+    }
+    results[index] = Some(transcription);
+}
+
+/// A period of silence detected in the audio, in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Silence {
+    start: f64,
+    end: f64,
+}
+
+impl Silence {
+    fn midpoint(&self) -> f64 {
+        (self.start + self.end) / 2.0
+    }
+}
+
+/// One piece of a chunked upload, in seconds from the start of the file.
+///
+/// `cut` and `next_cut` are the chosen split points; `start` and `end` extend
+/// them by the overlap so words spanning a cut are heard whole by both sides.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Chunk {
+    index: usize,
+    start: f64,
+    end: f64,
+    cut: f64,
+    next_cut: f64,
+}
+
+/// Find silences in the audio using ffmpeg's silencedetect filter.
+///
+/// ffmpeg is optional; without it chunks are cut purely by size.
+fn detect_silences(audio_path: &Path) -> Result<Vec<Silence>> {
+    let filter = format!("silencedetect=noise={SILENCE_NOISE_DB}dB:d={SILENCE_MIN_SECS}");
+    let output = Command::new("ffmpeg")
+        .args([
+            "-hide_banner",
+            "-nostats",
+            "-i",
+            &audio_path.to_string_lossy(),
+            "-af",
+            &filter,
+            "-f",
+            "null",
+            "-",
+        ])
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => eyre::eyre!("ffmpeg not found"),
+            _ => eyre::eyre!("failed to run ffmpeg: {e}"),
+        })?;
+
+    if !output.status.success() {
+        bail!("ffmpeg silencedetect failed on {}", audio_path.display());
+    }
+
+    Ok(parse_silences(&String::from_utf8_lossy(&output.stderr)))
+}
+
+/// Parse `silence_start` / `silence_end` lines from silencedetect output
+fn parse_silences(log: &str) -> Vec<Silence> {
+    let mut silences = Vec::new();
+    let mut pending_start = None;
+
+    for line in log.lines() {
+        if let Some(rest) = line.split("silence_start:").nth(1) {
+            pending_start = rest.split_whitespace().next().and_then(|v| v.parse::<f64>().ok());
+        } else if let Some(rest) = line.split("silence_end:").nth(1) {
+            let end = rest.split_whitespace().next().and_then(|v| v.parse::<f64>().ok());
+            if let (Some(start), Some(end)) = (pending_start.take(), end) {
+                silences.push(Silence {
+                    start: start.max(0.0),
+                    end,
+                });
+            }
         }
+    }
 
-        let mut segments = transcribe_file(client, api_key, &chunk_path, model, lang).await?;
+    silences
+}
 
-        // Adjust timestamps for the offset
-        for seg in &mut segments {
-            seg.start += time_offset;
+/// Choose cut points so each chunk stays under the upload limit, preferring
+/// the middle of a silence shortly before each size-based limit.
+///
+/// `reach(start)` gives the furthest time a chunk beginning at `start` can
+/// extend to while staying within `CHUNK_TARGET_BYTES`.
+fn plan_chunks(duration: f64, silences: &[Silence], reach: impl Fn(f64) -> f64) -> Vec<Chunk> {
+    let mut cuts = vec![0.0];
+    let mut cursor = 0.0;
+    loop {
+        let limit = reach((cursor - CHUNK_OVERLAP_SECS).max(0.0)) - CHUNK_OVERLAP_SECS;
+        if limit >= duration {
+            break;
         }
+        // Always make progress, even if a single frame is over budget
+        let limit = limit.max(cursor + 1.0);
+        let earliest = (limit - SILENCE_SEARCH_WINDOW_SECS).max(cursor);
+        let cut = silences
+            .iter()
+            .map(Silence::midpoint)
+            .filter(|&m| m > earliest && m <= limit)
+            .fold(None, |best: Option<f64>, m| Some(best.map_or(m, |b| b.max(m))))
+            .unwrap_or(limit);
+        cuts.push(cut);
+        cursor = cut;
+    }
+    cuts.push(duration);
+
+    cuts.windows(2)
+        .enumerate()
+        .map(|(index, w)| Chunk {
+            index,
+            start: (w[0] - CHUNK_OVERLAP_SECS).max(0.0),
+            end: (w[1] + CHUNK_OVERLAP_SECS).min(duration),
+            cut: w[0],
+            next_cut: w[1],
+        })
+        .collect()
+}
 
-        time_offset = start_time + chunk_duration_secs as f64;
-        all_segments.extend(segments);
+/// Append a chunk's (already offset) segments, keeping only those centred
+/// inside the chunk's own span and trimming words repeated across the cut.
+fn stitch_segments(all: &mut Vec<Segment>, segments: Vec<Segment>, chunk: &Chunk) {
+    let mut first = true;
+    for mut seg in segments {
+        let mid = seg.start + seg.duration / 2.0;
+        if mid < chunk.cut || mid >= chunk.next_cut {
+            continue;
+        }
 
-        // Clean up chunk
-        let _ = std::fs::remove_file(&chunk_path);
+        if first && let Some(prev) = all.last() {
+            let n = overlap_word_count(&prev.text, &seg.text);
+            if n > 0 {
+                seg.text = seg.text.split_whitespace().skip(n).collect::<Vec<_>>().join(" ");
+            }
+        }
+        first = false;
+
+        if !seg.text.is_empty() {
+            all.push(seg);
+        }
     }
+}
+
+/// Number of words at the start of `next` that repeat the end of `prev`
+fn overlap_word_count(prev: &str, next: &str) -> usize {
+    let normalize = |w: &str| w.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+    let prev: Vec<String> = prev.split_whitespace().map(normalize).collect();
+    let next: Vec<String> = next.split_whitespace().map(normalize).collect();
+    let max = prev.len().min(next.len()).min(MAX_OVERLAP_WORDS);
 
-    Ok(all_segments)
+    (MIN_OVERLAP_WORDS..=max)
+        .rev()
+        .find(|&n| prev[prev.len() - n..] == next[..n])
+        .unwrap_or(0)
 }
 
 #[cfg(test)]
@@ -300,7 +1068,7 @@ mod tests {
             ]
         });
 
-        let segments = parse_whisper_response(&json).unwrap();
+        let segments = parse_whisper_response(&json).unwrap().segments;
         assert_eq!(segments.len(), 2);
         assert_eq!(segments[0].text, "Hello world.");
         assert!((segments[0].start - 0.0).abs() < f64::EPSILON);
@@ -308,13 +1076,55 @@ mod tests {
         assert_eq!(segments[1].text, "This is a test.");
     }
 
+    #[test]
+    fn test_parse_whisper_response_filters_hallucinations() {
+        let json = serde_json::json!({
+            "segments": [
+                {"start": 0.0, "end": 4.0, "text": " Welcome to the talk.",
+                 "avg_logprob": -0.2, "no_speech_prob": 0.01, "compression_ratio": 1.2},
+                {"start": 4.0, "end": 9.0, "text": " we will we will we will we will rock you",
+                 "avg_logprob": -0.5, "no_speech_prob": 0.02, "compression_ratio": 2.9},
+                {"start": 9.0, "end": 30.0, "text": " Thanks for watching!",
+                 "avg_logprob": -0.6, "no_speech_prob": 0.7, "compression_ratio": 0.9}
+            ]
+        });
+
+        let transcription = parse_whisper_response(&json).unwrap();
+        let segments = &transcription.segments;
+        assert_eq!(segments.len(), 2);
+        assert!(!segments[0].suspect);
+        assert!(segments[1].suspect);
+        assert_eq!(segments[1].text, "we will rock you");
+
+        let quality = transcription.quality.quality();
+        assert_eq!(quality.dropped, 1);
+        assert_eq!(quality.flagged, 1);
+        assert_eq!(quality.collapsed, 1);
+    }
+
+    #[test]
+    fn test_parse_detected_language() {
+        let json = serde_json::json!({"language": "japanese", "text": "こんにちは"});
+        assert_eq!(parse_detected_language(&json).as_deref(), Some("ja"));
+        let json = serde_json::json!({"language": "Klingon"});
+        assert_eq!(parse_detected_language(&json).as_deref(), Some("klingon"));
+        assert_eq!(parse_detected_language(&serde_json::json!({"text": "hi"})), None);
+    }
+
+    #[test]
+    fn test_majority_language() {
+        assert_eq!(majority_language(["fr", "en", "fr"].into_iter()).as_deref(), Some("fr"));
+        assert_eq!(majority_language(["de", "en"].into_iter()).as_deref(), Some("de"));
+        assert_eq!(majority_language(std::iter::empty()), None);
+    }
+
     #[test]
     fn test_parse_whisper_response_plain_text() {
         let json = serde_json::json!({
             "text": "Just plain text."
         });
 
-        let segments = parse_whisper_response(&json).unwrap();
+        let segments = parse_whisper_response(&json).unwrap().segments;
         assert_eq!(segments.len(), 1);
         assert_eq!(segments[0].text, "Just plain text.");
     }
@@ -333,14 +1143,242 @@ mod tests {
             ]
         });
 
-        let segments = parse_whisper_response(&json).unwrap();
+        let segments = parse_whisper_response(&json).unwrap().segments;
         assert!(segments.is_empty());
     }
 
+    #[test]
+    fn test_parse_silences() {
+        let log = "\
+[silencedetect @ 0x1] silence_start: 12.5
+[silencedetect @ 0x1] silence_end: 13.25 | silence_duration: 0.75
+size=N/A time=00:00:20.00 bitrate=N/A
+[silencedetect @ 0x1] silence_start: -0.01
+[silencedetect @ 0x1] silence_end: 0.6 | silence_duration: 0.61
+[silencedetect @ 0x1] silence_start: 19.0";
+
+        let silences = parse_silences(log);
+        assert_eq!(
+            silences,
+            vec![
+                Silence {
+                    start: 12.5,
+                    end: 13.25
+                },
+                Silence { start: 0.0, end: 0.6 },
+            ]
+        );
+    }
+
+    /// Constant 64 kbps audio: 8000 bytes per second
+    fn constant_reach(start: f64) -> f64 {
+        start + CHUNK_TARGET_BYTES as f64 / 8_000.0
+    }
+
+    #[test]
+    fn test_plan_chunks_prefers_silence_before_target() {
+        let limit = constant_reach(0.0) - CHUNK_OVERLAP_SECS;
+        let duration = limit * 1.5;
+        let silence = Silence {
+            start: limit - 30.0,
+            end: limit - 28.0,
+        };
+        let chunks = plan_chunks(duration, &[silence], constant_reach);
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].cut, 0.0);
+        assert_eq!(chunks[0].start, 0.0);
+        assert!((chunks[0].next_cut - silence.midpoint()).abs() < 1e-6);
+        assert!((chunks[0].end - (silence.midpoint() + CHUNK_OVERLAP_SECS)).abs() < 1e-6);
+        assert!((chunks[1].start - (silence.midpoint() - CHUNK_OVERLAP_SECS)).abs() < 1e-6);
+        assert_eq!(chunks[1].end, duration);
+    }
+
+    #[test]
+    fn test_plan_chunks_without_silence_cuts_at_target() {
+        let duration = 3.0 * 3600.0;
+        let chunks = plan_chunks(duration, &[], constant_reach);
+
+        assert!(chunks.len() >= 4);
+        for pair in chunks.windows(2) {
+            assert!((pair[0].next_cut - pair[1].cut).abs() < 1e-9);
+        }
+        for chunk in &chunks {
+            let bytes = (chunk.end - chunk.start) * 8_000.0;
+            assert!(bytes <= CHUNK_TARGET_BYTES as f64 + 1e-6);
+        }
+        assert_eq!(chunks.last().unwrap().end, duration);
+    }
+
+    #[test]
+    fn test_plan_chunks_small_file_single_chunk() {
+        let chunks = plan_chunks(600.0, &[], constant_reach);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].start, 0.0);
+        assert_eq!(chunks[0].end, 600.0);
+    }
+
+    #[test]
+    fn test_overlap_word_count() {
+        assert_eq!(
+            overlap_word_count("we deploy it to Kubernetes.", "to kubernetes and then"),
+            2
+        );
+        assert_eq!(overlap_word_count("hello world", "something else"), 0);
+        // A single shared word is too weak a signal to trim
+        assert_eq!(overlap_word_count("into the", "the cluster"), 0);
+    }
+
+    #[test]
+    fn test_stitch_segments_drops_overlap() {
+        let seg = |text: &str, start: f64, duration: f64| Segment {
+            text: text.to_string(),
+            start,
+            duration,
+            ..Default::default()
+        };
+        let mut all = vec![seg("and then we deploy it", 96.0, 3.0)];
+        let chunk = Chunk {
+            index: 1,
+            start: 98.0,
+            end: 200.0,
+            cut: 100.0,
+            next_cut: 200.0,
+        };
+        let next = vec![
+            seg("deploy it", 98.0, 1.0),
+            seg("deploy it to production", 99.5, 2.0),
+            seg("and that's it", 102.0, 2.0),
+        ];
+
+        stitch_segments(&mut all, next, &chunk);
+        let texts: Vec<_> = all.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, vec!["and then we deploy it", "to production", "and that's it"]);
+    }
+
+    #[test]
+    fn test_description_keywords() {
+        let description = "In this talk we deploy Kubernetes with ArgoCD. Links: https://Example.com/Path \
+                           Thanks to Jane Doe and the #devops crowd. See also gRPC and k8s.";
+        let keywords = description_keywords(description);
+        assert_eq!(keywords, vec!["Kubernetes", "ArgoCD", "Jane", "Doe", "devops", "gRPC"]);
+    }
+
+    #[test]
+    fn test_parse_metadata_chapters() {
+        let metadata: VideoMetadata = serde_json::from_str(
+            r#"{"title": "Talk", "duration": 3600, "chapters": [
+                {"start_time": 0.0, "end_time": 600.0, "title": "Intro"},
+                {"start_time": 600.0, "end_time": 3600.0, "title": "Main"}]}"#,
+        )
+        .unwrap();
+        let chapters = metadata.chapters.unwrap();
+        assert_eq!(chapters[1].title, "Main");
+        assert_eq!(chapters[1].start_time, 600.0);
+
+        let metadata: VideoMetadata = serde_json::from_str(r#"{"title": "Talk", "chapters": null}"#).unwrap();
+        assert!(metadata.chapters.is_none());
+    }
+
+    #[test]
+    fn test_build_prompt() {
+        let metadata = VideoMetadata {
+            title: "Scaling Postgres at Acme".to_string(),
+            description: "We migrated to CockroachDB last year.".to_string(),
+            tags: vec!["postgres".to_string(), "Citus".to_string()],
+            ..Default::default()
+        };
+        let glossary = vec!["Postgres".to_string(), "PgBouncer".to_string()];
+
+        let prompt = build_prompt(&metadata, &glossary);
+        assert_eq!(
+            prompt,
+            "Scaling Postgres at Acme. Postgres, PgBouncer, Citus, CockroachDB"
+        );
+    }
+
+    #[test]
+    fn test_build_prompt_is_bounded() {
+        let metadata = VideoMetadata {
+            title: "Title".to_string(),
+            ..Default::default()
+        };
+        let glossary: Vec<String> = (0..500).map(|i| format!("Term{i}")).collect();
+
+        let prompt = build_prompt(&metadata, &glossary);
+        assert!(prompt.len() <= MAX_PROMPT_CHARS - CONTEXT_TAIL_CHARS);
+        assert!(prompt.starts_with("Title. Term0, Term1"));
+        assert!(!prompt.ends_with(','));
+    }
+
+    #[test]
+    fn test_chunk_prompt_carries_previous_tail() {
+        assert_eq!(chunk_prompt("Base.", None), "Base.");
+
+        let previous = vec![Segment {
+            text: "short context".to_string(),
+            ..Default::default()
+        }];
+        assert_eq!(chunk_prompt("Base.", Some(&previous)), "Base. short context");
+
+        let long = vec![Segment {
+            text: "word ".repeat(200),
+            ..Default::default()
+        }];
+        let prompt = chunk_prompt("", Some(&long));
+        assert!(prompt.len() <= CONTEXT_TAIL_CHARS);
+        assert!(prompt.starts_with("word"));
+    }
+
+    #[test]
+    fn test_parse_whisper_response_diarized_json() {
+        let json = serde_json::json!({
+            "text": "Welcome back. Thanks for having me.",
+            "segments": [
+                {
+                    "type": "transcript.text.segment",
+                    "id": "seg_0",
+                    "start": 0.0,
+                    "end": 1.2,
+                    "text": "Welcome back.",
+                    "speaker": "A"
+                },
+                {
+                    "type": "transcript.text.segment",
+                    "id": "seg_1",
+                    "start": 1.4,
+                    "end": 3.0,
+                    "text": "Thanks for having me.",
+                    "speaker": "B"
+                }
+            ]
+        });
+
+        let segments = parse_whisper_response(&json).unwrap().segments;
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].speaker.as_deref(), Some("A"));
+        assert_eq!(segments[1].speaker.as_deref(), Some("B"));
+        assert_eq!(segments[1].text, "Thanks for having me.");
+    }
+
     #[test]
     fn test_whisper_model_api_names() {
         assert_eq!(WhisperModel::Gpt4oMiniTranscribe.api_name(), "gpt-4o-mini-transcribe");
         assert_eq!(WhisperModel::Gpt4oTranscribe.api_name(), "gpt-4o-transcribe");
+        assert_eq!(
+            WhisperModel::Gpt4oTranscribeDiarize.api_name(),
+            "gpt-4o-transcribe-diarize"
+        );
         assert_eq!(WhisperModel::Whisper1.api_name(), "whisper-1");
     }
+
+    #[test]
+    fn test_whisper_model_from_str() {
+        assert_eq!(
+            "gpt-4o-transcribe-diarize".parse::<WhisperModel>().unwrap(),
+            WhisperModel::Gpt4oTranscribeDiarize
+        );
+        assert_eq!("whisper-1".parse::<WhisperModel>().unwrap(), WhisperModel::Whisper1);
+        assert!("whisper-2".parse::<WhisperModel>().is_err());
+    }
 }
@@ -0,0 +1,214 @@
+use std::path::PathBuf;
+
+use eyre::Result;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{Chunk, Transcription};
+
+fn jobs_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from(".cache"))
+        .join("ytx")
+        .join("jobs")
+}
+
+/// Hex-encoded SHA-256 of the audio, used to tell whether a job still applies
+pub fn checksum(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// What a job was started with; a resume is only valid if all of this matches
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub audio_sha256: String,
+    pub model: String,
+    pub lang: String,
+    #[serde(default)]
+    pub translate: bool,
+    pub chunks: Vec<Chunk>,
+}
+
+/// A chunked transcription in progress, checkpointed one chunk at a time
+/// under `~/.cache/ytx/jobs/{video_id}/`
+#[derive(Debug)]
+pub struct Job {
+    dir: PathBuf,
+    manifest: Manifest,
+}
+
+impl Job {
+    /// Resume the job for this video if it was started on the same audio with
+    /// the same settings, otherwise start a new one using `plan`.
+    pub fn resume_or_create(
+        video_id: &str,
+        audio_sha256: &str,
+        model: &str,
+        lang: &str,
+        translate: bool,
+        plan: impl FnOnce() -> Vec<Chunk>,
+    ) -> Result<Self> {
+        Self::resume_or_create_in(jobs_dir().join(video_id), audio_sha256, model, lang, translate, plan)
+    }
+
+    fn resume_or_create_in(
+        dir: PathBuf,
+        audio_sha256: &str,
+        model: &str,
+        lang: &str,
+        translate: bool,
+        plan: impl FnOnce() -> Vec<Chunk>,
+    ) -> Result<Self> {
+        let manifest_path = dir.join("job.json");
+
+        if let Some(manifest) = std::fs::read_to_string(&manifest_path)
+            .ok()
+            .and_then(|data| serde_json::from_str::<Manifest>(&data).ok())
+            .filter(|m| {
+                m.audio_sha256 == audio_sha256 && m.model == model && m.lang == lang && m.translate == translate
+            })
+        {
+            debug!("Resuming Whisper job: {}", dir.display());
+            return Ok(Self { dir, manifest });
+        }
+
+        // Stale or missing: start over
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+
+        let manifest = Manifest {
+            audio_sha256: audio_sha256.to_string(),
+            model: model.to_string(),
+            lang: lang.to_string(),
+            translate,
+            chunks: plan(),
+        };
+        std::fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)?;
+        debug!("Started Whisper job: {}", dir.display());
+
+        Ok(Self { dir, manifest })
+    }
+
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.manifest.chunks
+    }
+
+    fn chunk_path(&self, index: usize) -> PathBuf {
+        self.dir.join(format!("chunk-{index}.json"))
+    }
+
+    /// A chunk that was already transcribed, if any
+    pub fn completed(&self, index: usize) -> Option<Transcription> {
+        let data = std::fs::read_to_string(self.chunk_path(index)).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    /// Record a finished chunk so it is never uploaded again
+    pub fn checkpoint(&self, index: usize, transcription: &Transcription) -> Result<()> {
+        // Write then rename, so an interrupted write can't leave a bad checkpoint
+        let path = self.chunk_path(index);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string(transcription)?)?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Remove the job once its transcript has been assembled
+    pub fn finish(self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.dir) {
+            debug!("Failed to remove job directory {}: {e}", self.dir.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Segment;
+
+    fn chunk(index: usize, cut: f64, next_cut: f64) -> Chunk {
+        Chunk {
+            index,
+            start: cut,
+            end: next_cut,
+            cut,
+            next_cut,
+        }
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ytx-job-test-{name}-{}", std::process::id()))
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(
+            checksum(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_resume_keeps_completed_chunks_and_plan() {
+        let dir = test_dir("resume");
+        let plan = vec![chunk(0, 0.0, 10.0), chunk(1, 10.0, 20.0)];
+
+        let job = Job::resume_or_create_in(dir.clone(), "abc", "whisper-1", "en", false, || plan.clone()).unwrap();
+        let transcription = Transcription {
+            segments: vec![Segment {
+                text: "hello".to_string(),
+                start: 1.0,
+                duration: 2.0,
+                ..Default::default()
+            }],
+            language: Some("en".to_string()),
+            ..Default::default()
+        };
+        job.checkpoint(0, &transcription).unwrap();
+
+        // A resumed job keeps the stored plan rather than re-planning
+        let job = Job::resume_or_create_in(dir.clone(), "abc", "whisper-1", "en", false, Vec::new).unwrap();
+        assert_eq!(job.chunks(), plan.as_slice());
+        assert_eq!(job.completed(0).unwrap().segments[0].text, "hello");
+        assert!(job.completed(1).is_none());
+
+        job.finish();
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_changed_audio_restarts_job() {
+        let dir = test_dir("restart");
+        let job = Job::resume_or_create_in(dir.clone(), "abc", "whisper-1", "en", false, || {
+            vec![chunk(0, 0.0, 5.0)]
+        })
+        .unwrap();
+        job.checkpoint(0, &Transcription::default()).unwrap();
+
+        let job = Job::resume_or_create_in(dir.clone(), "def", "whisper-1", "en", false, || {
+            vec![chunk(0, 0.0, 6.0)]
+        })
+        .unwrap();
+        assert!(job.completed(0).is_none());
+        assert_eq!(job.chunks()[0].end, 6.0);
+
+        job.finish();
+    }
+
+    #[test]
+    fn test_translation_does_not_resume_transcription() {
+        let dir = test_dir("translate");
+        let job = Job::resume_or_create_in(dir.clone(), "abc", "whisper-1", "fr", false, || {
+            vec![chunk(0, 0.0, 5.0)]
+        })
+        .unwrap();
+        job.checkpoint(0, &Transcription::default()).unwrap();
+
+        let job =
+            Job::resume_or_create_in(dir.clone(), "abc", "whisper-1", "fr", true, || vec![chunk(0, 0.0, 5.0)]).unwrap();
+        assert!(job.completed(0).is_none());
+
+        job.finish();
+    }
+}
@@ -0,0 +1,202 @@
+use std::path::Path;
+use std::process::Command;
+
+use eyre::{Result, bail};
+use log::debug;
+use serde::Deserialize;
+
+use super::quality::{self, Tally};
+use super::{VideoMetadata, WhisperOptions, languages, source_audio};
+use crate::workspace::Workspace;
+use crate::{AUTO_LANG, Segment, Transcript, TranscriptSource};
+
+/// whisper.cpp only reads 16 kHz mono PCM WAV
+const WAV_SAMPLE_RATE: &str = "16000";
+
+/// whisper.cpp's `--output-json` file
+#[derive(Debug, Deserialize)]
+struct Output {
+    #[serde(default)]
+    result: Option<OutputResult>,
+    #[serde(default)]
+    transcription: Vec<OutputSegment>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OutputResult {
+    language: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OutputSegment {
+    /// Start and end in milliseconds
+    offsets: Offsets,
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Offsets {
+    from: u64,
+    to: u64,
+}
+
+/// Transcribe a video on this machine with whisper.cpp, using the ggml model
+/// at `model_path`. Free and offline apart from the download, but as slow as
+/// the machine. `opts` supplies the language, translation and audio settings;
+/// the API model, glossary and preprocessing don't apply.
+pub async fn transcribe_local(
+    video_id: &str,
+    metadata: &VideoMetadata,
+    model_path: &Path,
+    command: &str,
+    opts: &WhisperOptions,
+) -> Result<Transcript> {
+    let video_id = video_id.to_string();
+    let metadata = metadata.clone();
+    let model_path = model_path.to_path_buf();
+    let command = command.to_string();
+    let opts = opts.clone();
+    // The download, conversion and recognition can take as long as the
+    // video, so they run on a thread of their own, not a runtime worker
+    tokio::task::spawn_blocking(move || run(&video_id, &metadata, &model_path, &command, &opts)).await?
+}
+
+fn run(
+    video_id: &str,
+    metadata: &VideoMetadata,
+    model_path: &Path,
+    command: &str,
+    opts: &WhisperOptions,
+) -> Result<Transcript> {
+    if !model_path.exists() {
+        bail!("whisper.cpp model not found: {}", model_path.display());
+    }
+
+    let workspace = Workspace::create(&opts.temp_root)?;
+    let audio_path = source_audio(video_id, &workspace, opts)?;
+    let wav_path = workspace.file("audio.wav");
+    to_wav(&audio_path, &wav_path)?;
+
+    let out_stem = workspace.file("transcript");
+    let lang = if opts.lang == AUTO_LANG { "auto" } else { &opts.lang };
+    let mut cmd = Command::new(command);
+    cmd.arg("-m")
+        .arg(model_path)
+        .arg("-f")
+        .arg(&wav_path)
+        .args(["-l", lang, "-oj", "-np", "-of"])
+        .arg(&out_stem);
+    if opts.translate {
+        cmd.arg("-tr");
+    }
+    debug!("Running {command} on {}", wav_path.display());
+    let output = cmd.output().map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => eyre::eyre!(
+            "{command} not found. Build whisper.cpp (https://github.com/ggml-org/whisper.cpp) \
+             or set the local tier's 'command'"
+        ),
+        _ => eyre::eyre!("failed to run {command}: {e}"),
+    })?;
+    if !output.status.success() {
+        bail!(
+            "{command} exited with status {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let json = std::fs::read_to_string(out_stem.with_extension("json"))?;
+    let (mut segments, detected) = parse_output(&json)?;
+
+    let mut tally = Tally::default();
+    tally.segments = segments.len();
+    tally.collapsed += quality::collapse_repeated_segments(&mut segments);
+
+    let (language, source_language) = languages(opts, metadata, detected.as_deref());
+    Ok(Transcript {
+        video_id: video_id.to_string(),
+        title: metadata.title.clone(),
+        language,
+        source_language,
+        source: TranscriptSource::Local,
+        segments,
+        speakers: Default::default(),
+        quality: Some(tally.quality()),
+    })
+}
+
+fn to_wav(input: &Path, output: &Path) -> Result<()> {
+    let status = Command::new("ffmpeg")
+        .args(["-hide_banner", "-nostdin", "-loglevel", "error", "-y", "-i"])
+        .arg(input)
+        .args(["-ac", "1", "-ar", WAV_SAMPLE_RATE, "-c:a", "pcm_s16le"])
+        .arg(output)
+        .status()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => eyre::eyre!("ffmpeg not found (required for local transcription)"),
+            _ => eyre::eyre!("failed to run ffmpeg: {e}"),
+        })?;
+    if !status.success() {
+        bail!("ffmpeg failed to convert {}", input.display());
+    }
+    Ok(())
+}
+
+/// Segments and detected language from whisper.cpp's JSON output
+fn parse_output(json: &str) -> Result<(Vec<Segment>, Option<String>)> {
+    let output: Output = serde_json::from_str(json)?;
+    let segments = output
+        .transcription
+        .into_iter()
+        .filter_map(|s| {
+            let text = s.text.trim();
+            if text.is_empty() {
+                return None;
+            }
+            let start = s.offsets.from as f64 / 1000.0;
+            Some(Segment {
+                text: text.to_string(),
+                start,
+                duration: (s.offsets.to.saturating_sub(s.offsets.from)) as f64 / 1000.0,
+                ..Default::default()
+            })
+        })
+        .collect();
+    let language = output.result.and_then(|r| r.language);
+    Ok((segments, language))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_output() {
+        let json = r#"{
+            "result": {"language": "de"},
+            "transcription": [
+                {"timestamps": {"from": "00:00:00,000", "to": "00:00:02,500"},
+                 "offsets": {"from": 0, "to": 2500}, "text": " Guten Tag."},
+                {"timestamps": {"from": "00:00:02,500", "to": "00:00:03,000"},
+                 "offsets": {"from": 2500, "to": 3000}, "text": "  "},
+                {"timestamps": {"from": "00:00:03,000", "to": "00:00:05,250"},
+                 "offsets": {"from": 3000, "to": 5250}, "text": " Wie geht's?"}
+            ]
+        }"#;
+        let (segments, language) = parse_output(json).unwrap();
+        assert_eq!(language.as_deref(), Some("de"));
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].text, "Guten Tag.");
+        assert_eq!(segments[0].duration, 2.5);
+        assert_eq!(segments[1].start, 3.0);
+        assert_eq!(segments[1].duration, 2.25);
+    }
+
+    #[test]
+    fn test_parse_output_without_language() {
+        let (segments, language) = parse_output(r#"{"transcription": []}"#).unwrap();
+        assert!(segments.is_empty());
+        assert_eq!(language, None);
+        assert!(parse_output("not json").is_err());
+    }
+}
@@ -0,0 +1,337 @@
+use std::path::Path;
+
+use eyre::{Result, bail};
+
+/// Bitrates in kbps, indexed by the header's 4-bit bitrate field
+const BITRATES_V1_L1: [u32; 15] = [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448];
+const BITRATES_V1_L2: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384];
+const BITRATES_V1_L3: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+const BITRATES_V2_L1: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256];
+const BITRATES_V2_L23: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Version {
+    Mpeg1,
+    Mpeg2,
+    Mpeg25,
+}
+
+/// A single MPEG audio frame located in the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    pub offset: usize,
+    pub len: usize,
+    pub samples: u32,
+    pub sample_rate: u32,
+}
+
+impl Frame {
+    pub fn duration(&self) -> f64 {
+        self.samples as f64 / self.sample_rate as f64
+    }
+}
+
+/// Frame index of an MP3 file, for exact timing and frame-aligned splitting
+#[derive(Debug)]
+pub struct Mp3 {
+    data: Vec<u8>,
+    frames: Vec<Frame>,
+    /// Start time of each frame in seconds, plus the total duration at the end
+    times: Vec<f64>,
+}
+
+impl Mp3 {
+    /// Read and index an MP3 file
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let frames = parse_frames(&data);
+        if frames.is_empty() {
+            bail!("no MPEG audio frames found");
+        }
+
+        let mut times = Vec::with_capacity(frames.len() + 1);
+        let mut t = 0.0;
+        for frame in &frames {
+            times.push(t);
+            t += frame.duration();
+        }
+        times.push(t);
+
+        Ok(Self { data, frames, times })
+    }
+
+    /// Exact playing time in seconds
+    pub fn duration(&self) -> f64 {
+        *self.times.last().unwrap_or(&0.0)
+    }
+
+    /// Raw file contents
+    pub fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Index of the frame playing at `t` seconds; the last frame for times
+    /// at or past the end
+    fn frame_at(&self, t: f64) -> usize {
+        let i = self.times.partition_point(|&start| start <= t);
+        i.saturating_sub(1).min(self.frames.len().saturating_sub(1))
+    }
+
+    /// Furthest time reachable from `start` without exceeding `max_bytes`
+    pub fn reach(&self, start: f64, max_bytes: u64) -> f64 {
+        let first = self.frame_at(start);
+        let mut bytes = 0u64;
+        for (i, frame) in self.frames.iter().enumerate().skip(first) {
+            bytes += frame.len as u64;
+            if bytes > max_bytes {
+                return self.times[i];
+            }
+        }
+        self.duration()
+    }
+
+    /// Write the frames covering `start..end` to `path`.
+    ///
+    /// Returns the time of the first written frame, which is where the
+    /// output's own timeline begins.
+    pub fn write_range(&self, start: f64, end: f64, path: &Path) -> Result<f64> {
+        let first = self.frame_at(start);
+        let last = self
+            .times
+            .partition_point(|&t| t < end)
+            .clamp(first + 1, self.frames.len());
+
+        let from = self.frames[first].offset;
+        let to = self.frames[last - 1].offset + self.frames[last - 1].len;
+        std::fs::write(path, &self.data[from..to])?;
+
+        Ok(self.times[first])
+    }
+}
+
+/// Locate every audio frame, skipping ID3 tags, VBR info frames and junk
+pub fn parse_frames(data: &[u8]) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    let mut pos = id3v2_len(data);
+
+    while pos + 4 <= data.len() {
+        if data[pos..].starts_with(b"TAG") && data.len() - pos == 128 {
+            break; // ID3v1 trailer
+        }
+
+        match parse_header(&data[pos..]) {
+            // Require the following frame to line up too (or EOF), so stray
+            // sync bytes inside junk aren't mistaken for a frame
+            Some(frame) if pos + frame.len <= data.len() && lines_up(data, pos + frame.len) => {
+                let frame = Frame { offset: pos, ..frame };
+                if !(frames.is_empty() && is_info_frame(&data[pos..pos + frame.len])) {
+                    frames.push(frame);
+                }
+                pos += frame.len;
+            }
+            _ => pos += 1,
+        }
+    }
+
+    frames
+}
+
+fn lines_up(data: &[u8], next: usize) -> bool {
+    next + 4 > data.len() || data[next..].starts_with(b"TAG") || parse_header(&data[next..]).is_some()
+}
+
+/// Size of a leading ID3v2 tag, including header and optional footer
+fn id3v2_len(data: &[u8]) -> usize {
+    if data.len() < 10 || !data.starts_with(b"ID3") {
+        return 0;
+    }
+    let size = data[6..10]
+        .iter()
+        .fold(0usize, |acc, &b| (acc << 7) | (b & 0x7f) as usize);
+    let footer = if data[5] & 0x10 != 0 { 10 } else { 0 };
+    (10 + size + footer).min(data.len())
+}
+
+/// Xing/Info/VBRI frames carry encoder metadata rather than audio
+fn is_info_frame(frame: &[u8]) -> bool {
+    let head = &frame[..frame.len().min(64)];
+    head.windows(4).any(|w| w == b"Xing" || w == b"Info" || w == b"VBRI")
+}
+
+/// Decode a 4-byte MPEG audio frame header (offset is filled in by the caller)
+fn parse_header(bytes: &[u8]) -> Option<Frame> {
+    if bytes.len() < 4 || bytes[0] != 0xFF || bytes[1] & 0xE0 != 0xE0 {
+        return None;
+    }
+
+    let version = match (bytes[1] >> 3) & 0x03 {
+        0 => Version::Mpeg25,
+        2 => Version::Mpeg2,
+        3 => Version::Mpeg1,
+        _ => return None,
+    };
+    let layer = match (bytes[1] >> 1) & 0x03 {
+        1 => 3,
+        2 => 2,
+        3 => 1,
+        _ => return None,
+    };
+
+    let bitrate_index = (bytes[2] >> 4) as usize;
+    let rate_index = ((bytes[2] >> 2) & 0x03) as usize;
+    if bitrate_index == 0 || bitrate_index == 15 || rate_index == 3 {
+        return None; // free-format or invalid
+    }
+    let padding = ((bytes[2] >> 1) & 0x01) as u32;
+
+    let kbps = match (version, layer) {
+        (Version::Mpeg1, 1) => BITRATES_V1_L1[bitrate_index],
+        (Version::Mpeg1, 2) => BITRATES_V1_L2[bitrate_index],
+        (Version::Mpeg1, _) => BITRATES_V1_L3[bitrate_index],
+        (_, 1) => BITRATES_V2_L1[bitrate_index],
+        _ => BITRATES_V2_L23[bitrate_index],
+    };
+    let bitrate = kbps * 1000;
+
+    let sample_rate = match version {
+        Version::Mpeg1 => [44100, 48000, 32000][rate_index],
+        Version::Mpeg2 => [22050, 24000, 16000][rate_index],
+        Version::Mpeg25 => [11025, 12000, 8000][rate_index],
+    };
+
+    let (samples, len) = match (layer, version) {
+        (1, _) => (384, (12 * bitrate / sample_rate + padding) * 4),
+        (2, _) | (3, Version::Mpeg1) => (1152, 144 * bitrate / sample_rate + padding),
+        _ => (576, 72 * bitrate / sample_rate + padding),
+    };
+
+    Some(Frame {
+        offset: 0,
+        len: len as usize,
+        samples,
+        sample_rate,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// MPEG-1 Layer III, 128 kbps, 44.1 kHz, no padding: 417-byte frames
+    const HEADER: [u8; 4] = [0xFF, 0xFB, 0x90, 0x00];
+    const FRAME_LEN: usize = 417;
+
+    fn frames(n: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        for _ in 0..n {
+            data.extend_from_slice(&HEADER);
+            data.resize(data.len() + FRAME_LEN - 4, 0);
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let frame = parse_header(&HEADER).unwrap();
+        assert_eq!(frame.len, FRAME_LEN);
+        assert_eq!(frame.samples, 1152);
+        assert_eq!(frame.sample_rate, 44100);
+
+        // MPEG-2 Layer III, 32 kbps, 22.05 kHz, padded
+        let frame = parse_header(&[0xFF, 0xF3, 0x42, 0x00]).unwrap();
+        assert_eq!(frame.samples, 576);
+        assert_eq!(frame.sample_rate, 22050);
+        assert_eq!(frame.len, 72 * 32000 / 22050 + 1);
+    }
+
+    #[test]
+    fn test_parse_header_rejects_invalid() {
+        assert!(parse_header(&[0xFF, 0xFB, 0xF0, 0x00]).is_none()); // bad bitrate
+        assert!(parse_header(&[0xFF, 0xFB, 0x9C, 0x00]).is_none()); // bad sample rate
+        assert!(parse_header(&[0xFF, 0xE9, 0x90, 0x00]).is_none()); // reserved version
+        assert!(parse_header(&[0x00, 0xFB, 0x90, 0x00]).is_none());
+    }
+
+    #[test]
+    fn test_duration() {
+        let mp3 = Mp3::from_bytes(frames(100)).unwrap();
+        assert_eq!(mp3.frames().len(), 100);
+        assert!((mp3.duration() - 100.0 * 1152.0 / 44100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_skips_id3_and_junk() {
+        let mut data = b"ID3\x04\x00\x00\x00\x00\x00\x05hello".to_vec();
+        data.extend_from_slice(&[0xFF, 0x00, 0x12]);
+        data.extend(frames(3));
+        let mut trailer = b"TAG".to_vec();
+        trailer.resize(128, b' ');
+        data.extend(trailer);
+
+        let parsed = parse_frames(&data);
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0].offset, 18);
+    }
+
+    #[test]
+    fn test_skips_info_frame() {
+        let mut data = frames(4);
+        data[36..40].copy_from_slice(b"Info");
+        let parsed = parse_frames(&data);
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0].offset, FRAME_LEN);
+    }
+
+    #[test]
+    fn test_no_frames() {
+        assert!(Mp3::from_bytes(vec![0u8; 1000]).is_err());
+    }
+
+    #[test]
+    fn test_reach() {
+        let mp3 = Mp3::from_bytes(frames(10)).unwrap();
+        let frame_secs = 1152.0 / 44100.0;
+        assert!((mp3.reach(0.0, 3 * FRAME_LEN as u64) - 3.0 * frame_secs).abs() < 1e-9);
+        assert!((mp3.reach(2.0 * frame_secs, 2 * FRAME_LEN as u64) - 4.0 * frame_secs).abs() < 1e-9);
+        assert!((mp3.reach(0.0, u64::MAX) - mp3.duration()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_write_range_aligns_to_frames() {
+        let mp3 = Mp3::from_bytes(frames(10)).unwrap();
+        let frame_secs = 1152.0 / 44100.0;
+        let path = std::env::temp_dir().join(format!("ytx-mp3-test-{}.mp3", std::process::id()));
+
+        let start = mp3.write_range(2.5 * frame_secs, 5.5 * frame_secs, &path).unwrap();
+        let written = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!((start - 2.0 * frame_secs).abs() < 1e-9);
+        assert_eq!(written.len(), 4 * FRAME_LEN);
+        assert_eq!(parse_frames(&written).len(), 4);
+    }
+
+    #[test]
+    fn test_range_at_end_of_file() {
+        let mp3 = Mp3::from_bytes(frames(10)).unwrap();
+        let frame_secs = 1152.0 / 44100.0;
+        assert_eq!(mp3.frame_at(mp3.duration()), 9);
+        assert_eq!(mp3.frame_at(mp3.duration() + 5.0), 9);
+        assert!((mp3.reach(mp3.duration(), u64::MAX) - mp3.duration()).abs() < 1e-9);
+
+        let path = std::env::temp_dir().join(format!("ytx-mp3-end-test-{}.mp3", std::process::id()));
+        let start = mp3.write_range(mp3.duration(), mp3.duration() + 1.0, &path).unwrap();
+        let written = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!((start - 9.0 * frame_secs).abs() < 1e-9);
+        assert_eq!(written.len(), FRAME_LEN);
+    }
+}
@@ -0,0 +1,414 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use eyre::{Result, bail};
+use log::debug;
+
+use super::Silence;
+use super::job;
+use super::mp3::Mp3;
+use crate::Segment;
+use crate::workspace::Workspace;
+
+/// Shortest silence worth cutting out
+const TRIM_MIN_SILENCE_SECS: f64 = 1.0;
+
+/// Silence left on each side of a cut, so word edges aren't clipped
+const TRIM_PADDING_SECS: f64 = 0.25;
+
+/// Output encoding: mono 16 kHz Opus, which is all speech recognition needs
+const SAMPLE_RATE: &str = "16000";
+const OPUS_BITRATE: &str = "24k";
+
+/// Fastest supported speed-up; ffmpeg's atempo goes no higher in one stage
+/// on older builds, and recognition suffers well before that
+pub const MAX_SPEED: f64 = 2.0;
+
+/// How to shrink audio before upload, since transcription is billed per minute
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Preprocess {
+    /// Cut out silences longer than a second
+    pub trim_silence: bool,
+    /// Playback speed-up factor (1.0 leaves the tempo alone)
+    pub speed: f64,
+}
+
+impl Default for Preprocess {
+    fn default() -> Self {
+        Self {
+            trim_silence: false,
+            speed: 1.0,
+        }
+    }
+}
+
+impl Preprocess {
+    pub fn is_enabled(&self) -> bool {
+        self.trim_silence || self.speed != 1.0
+    }
+
+    /// Upper bound on the audio seconds uploaded for `duration_secs` of
+    /// video; trimmed silence is only known after download
+    pub fn billed_secs(&self, duration_secs: f64) -> f64 {
+        duration_secs / self.speed
+    }
+}
+
+/// Parse a speed-up factor for `--speed`
+pub fn parse_speed(input: &str) -> Result<f64> {
+    let speed: f64 = input.parse().map_err(|_| eyre::eyre!("invalid speed '{input}'"))?;
+    if !(1.0..=MAX_SPEED).contains(&speed) {
+        bail!("speed must be between 1.0 and {MAX_SPEED:.1}");
+    }
+    Ok(speed)
+}
+
+/// A stretch of original audio kept in the processed file
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Span {
+    processed_start: f64,
+    original_start: f64,
+    original_end: f64,
+}
+
+/// Maps times in preprocessed audio back to times in the original video
+#[derive(Debug, Clone, PartialEq)]
+pub struct OffsetMap {
+    spans: Vec<Span>,
+    speed: f64,
+}
+
+impl OffsetMap {
+    /// Map for audio made by keeping `kept` (original-time intervals, in
+    /// order) and playing the result `speed` times faster
+    fn new(kept: &[(f64, f64)], speed: f64) -> Self {
+        let mut spans = Vec::with_capacity(kept.len());
+        let mut processed = 0.0;
+        for &(start, end) in kept {
+            spans.push(Span {
+                processed_start: processed,
+                original_start: start,
+                original_end: end,
+            });
+            processed += (end - start) / speed;
+        }
+        Self { spans, speed }
+    }
+
+    /// Length of the processed audio in seconds
+    pub fn processed_duration(&self) -> f64 {
+        self.spans
+            .last()
+            .map(|s| s.processed_start + (s.original_end - s.original_start) / self.speed)
+            .unwrap_or_default()
+    }
+
+    pub fn to_original(&self, t: f64) -> f64 {
+        let i = self.spans.partition_point(|s| s.processed_start <= t).saturating_sub(1);
+        match self.spans.get(i) {
+            Some(span) => {
+                (span.original_start + (t - span.processed_start).max(0.0) * self.speed).min(span.original_end)
+            }
+            None => t * self.speed,
+        }
+    }
+
+    /// Checksum a chunked job over the processed audio is keyed on, given
+    /// the `source` audio it was made from. ffmpeg's output can differ byte
+    /// for byte between runs of the same input, so this covers what the
+    /// audio was made from instead: the source, the settings and the kept
+    /// ranges.
+    pub fn job_checksum(&self, source: &[u8], settings: &Preprocess) -> String {
+        let kept: Vec<(f64, f64)> = self.spans.iter().map(|s| (s.original_start, s.original_end)).collect();
+        let recipe = format!(
+            "trim_silence={} speed={} kept={kept:?}",
+            settings.trim_silence, settings.speed
+        );
+        job::checksum(&[source, recipe.as_bytes()].concat())
+    }
+
+    /// Move segment timings from processed time to original time
+    pub fn remap(&self, segments: &mut [Segment]) {
+        for seg in segments {
+            let end = self.to_original(seg.start + seg.duration);
+            seg.start = self.to_original(seg.start);
+            seg.duration = (end - seg.start).max(0.0);
+        }
+    }
+}
+
+/// Original-time intervals left once long silences are cut down to padding
+fn keep_intervals(duration: f64, silences: &[Silence]) -> Vec<(f64, f64)> {
+    let mut kept = Vec::new();
+    let mut pos = 0.0;
+
+    for silence in silences.iter().filter(|s| s.end - s.start >= TRIM_MIN_SILENCE_SECS) {
+        let cut_start = (silence.start + TRIM_PADDING_SECS).max(pos);
+        let cut_end = (silence.end - TRIM_PADDING_SECS).min(duration);
+        if cut_end <= cut_start {
+            continue;
+        }
+        if cut_start > pos {
+            kept.push((pos, cut_start));
+        }
+        pos = cut_end;
+    }
+
+    if pos < duration {
+        kept.push((pos, duration));
+    }
+    kept
+}
+
+/// ffmpeg audio filter that keeps `kept` and applies the speed-up
+fn filter_graph(kept: Option<&[(f64, f64)]>, speed: f64) -> String {
+    let mut filters = Vec::new();
+    if let Some(kept) = kept {
+        let select = kept
+            .iter()
+            .map(|(start, end)| format!("between(t,{start:.3},{end:.3})"))
+            .collect::<Vec<_>>()
+            .join("+");
+        // Restamp the kept samples so they play back to back
+        filters.push(format!("aselect='{select}',asetpts=N/SR/TB"));
+    }
+    if speed != 1.0 {
+        filters.push(format!("atempo={speed}"));
+    }
+    if filters.is_empty() {
+        return "anull".to_string();
+    }
+    filters.join(",")
+}
+
+/// ffmpeg arguments applying `filter_graph`, which is written to a file in
+/// the workspace: a long talk can keep thousands of ranges, more than fit in
+/// one command-line argument
+fn filter_args(workspace: &Workspace, kept: Option<&[(f64, f64)]>, speed: f64) -> Result<Vec<String>> {
+    let script = workspace.file("filter.txt");
+    std::fs::write(&script, filter_graph(kept, speed))?;
+    Ok(vec![
+        "-filter_script:a".to_string(),
+        script.to_string_lossy().into_owned(),
+    ])
+}
+
+/// Shrink the downloaded MP3 into `audio.ogg` in the workspace, returning its
+/// path and the map back to original time
+pub fn prepare(audio_path: &Path, workspace: &Workspace, settings: &Preprocess) -> Result<(PathBuf, OffsetMap)> {
+    let duration = Mp3::open(audio_path)?.duration();
+
+    let kept = if settings.trim_silence {
+        let silences = super::detect_silences(audio_path)?;
+        keep_intervals(duration, &silences)
+    } else {
+        vec![(0.0, duration)]
+    };
+
+    let output = workspace.file("audio.ogg");
+    let filter = filter_args(
+        workspace,
+        settings.trim_silence.then_some(kept.as_slice()),
+        settings.speed,
+    )?;
+    let filter: Vec<&str> = filter.iter().map(String::as_str).collect();
+    encode(audio_path, &output, None, &filter)?;
+
+    let map = OffsetMap::new(&kept, settings.speed);
+    debug!(
+        "Preprocessed {duration:.1}s of audio to {:.1}s ({} kept spans, speed {})",
+        map.processed_duration(),
+        kept.len(),
+        settings.speed
+    );
+    Ok((output, map))
+}
+
+/// Encode `input`, or the `range` of it in seconds, as speech-grade Opus
+fn encode(input: &Path, output: &Path, range: Option<(f64, f64)>, filter_args: &[&str]) -> Result<()> {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-hide_banner", "-nostdin", "-loglevel", "error", "-y"]);
+    if let Some((start, end)) = range {
+        cmd.args(["-ss", &format!("{start:.3}"), "-t", &format!("{:.3}", end - start)]);
+    }
+    let status = cmd
+        .arg("-i")
+        .arg(input)
+        .args(filter_args)
+        .args(["-ac", "1", "-ar", SAMPLE_RATE, "-c:a", "libopus", "-b:a", OPUS_BITRATE])
+        .args(["-application", "voip"])
+        // Without these the Ogg muxer picks a random stream serial
+        .args(["-fflags", "+bitexact", "-flags:a", "+bitexact"])
+        .arg(output)
+        .status()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => eyre::eyre!("ffmpeg not found (required for audio preprocessing)"),
+            _ => eyre::eyre!("failed to run ffmpeg: {e}"),
+        })?;
+
+    if !status.success() {
+        bail!("ffmpeg failed to encode {}", input.display());
+    }
+    Ok(())
+}
+
+/// Preprocessed audio, split for upload by re-encoding time ranges
+#[derive(Debug)]
+pub struct Opus {
+    path: PathBuf,
+    duration: f64,
+    bytes_per_sec: f64,
+}
+
+impl Opus {
+    pub fn open(path: &Path) -> Result<Self> {
+        let duration = probe_duration(path)?;
+        let size = std::fs::metadata(path)?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            duration,
+            bytes_per_sec: size as f64 / duration.max(1.0),
+        })
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.duration
+    }
+
+    /// Time at which audio starting at `start` grows past `max_bytes`,
+    /// assuming a constant bitrate
+    pub fn reach(&self, start: f64, max_bytes: u64) -> f64 {
+        (start + max_bytes as f64 / self.bytes_per_sec).min(self.duration)
+    }
+
+    /// Write `start..end` to `path`, returning the time it starts at
+    pub fn write_range(&self, start: f64, end: f64, path: &Path) -> Result<f64> {
+        encode(&self.path, path, Some((start, end)), &[])?;
+        Ok(start)
+    }
+}
+
+fn probe_duration(path: &Path) -> Result<f64> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "format=duration", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .map_err(|e| eyre::eyre!("failed to run ffprobe: {e}"))?;
+
+    if !output.status.success() {
+        bail!("ffprobe failed on {}", path.display());
+    }
+
+    let text = String::from_utf8_lossy(&output.stdout);
+    text.trim()
+        .parse()
+        .map_err(|_| eyre::eyre!("ffprobe reported no duration for {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn silence(start: f64, end: f64) -> Silence {
+        Silence { start, end }
+    }
+
+    #[test]
+    fn test_keep_intervals_cuts_long_silences_only() {
+        let silences = [silence(10.0, 10.5), silence(20.0, 25.0), silence(58.0, 60.0)];
+        assert_eq!(
+            keep_intervals(60.0, &silences),
+            vec![(0.0, 20.25), (24.75, 58.25), (59.75, 60.0)]
+        );
+    }
+
+    #[test]
+    fn test_keep_intervals_leading_silence() {
+        let silences = [silence(0.0, 3.0)];
+        assert_eq!(keep_intervals(30.0, &silences), vec![(0.0, 0.25), (2.75, 30.0)]);
+    }
+
+    #[test]
+    fn test_offset_map_trim_and_speed() {
+        // Keep 0-10s and 20-30s, played at 2x: processed 0-5s then 5-10s
+        let map = OffsetMap::new(&[(0.0, 10.0), (20.0, 30.0)], 2.0);
+        assert_eq!(map.processed_duration(), 10.0);
+        assert_eq!(map.to_original(0.0), 0.0);
+        assert_eq!(map.to_original(2.5), 5.0);
+        assert_eq!(map.to_original(5.0), 20.0);
+        assert_eq!(map.to_original(7.5), 25.0);
+        assert_eq!(map.to_original(50.0), 30.0);
+    }
+
+    #[test]
+    fn test_job_checksum_is_stable_per_recipe() {
+        let settings = Preprocess {
+            trim_silence: true,
+            speed: 1.5,
+        };
+        let map = OffsetMap::new(&[(0.0, 10.0), (20.0, 30.0)], settings.speed);
+        let key = map.job_checksum(b"mp3 bytes", &settings);
+        assert_eq!(
+            key,
+            OffsetMap::new(&[(0.0, 10.0), (20.0, 30.0)], 1.5).job_checksum(b"mp3 bytes", &settings)
+        );
+
+        assert_ne!(map.job_checksum(b"other mp3", &settings), key);
+        let faster = Preprocess { speed: 2.0, ..settings };
+        assert_ne!(
+            OffsetMap::new(&[(0.0, 10.0), (20.0, 30.0)], 2.0).job_checksum(b"mp3 bytes", &faster),
+            key
+        );
+        let other_cuts = OffsetMap::new(&[(0.0, 10.0), (21.0, 30.0)], 1.5);
+        assert_ne!(other_cuts.job_checksum(b"mp3 bytes", &settings), key);
+    }
+
+    #[test]
+    fn test_remap_segments() {
+        let map = OffsetMap::new(&[(0.0, 10.0), (20.0, 30.0)], 1.0);
+        let mut segments = vec![Segment {
+            text: "after the cut".to_string(),
+            start: 12.0,
+            duration: 3.0,
+            ..Default::default()
+        }];
+        map.remap(&mut segments);
+        assert_eq!(segments[0].start, 22.0);
+        assert_eq!(segments[0].duration, 3.0);
+    }
+
+    #[test]
+    fn test_filter_graph() {
+        assert_eq!(filter_graph(None, 1.5), "atempo=1.5");
+        assert_eq!(
+            filter_graph(Some(&[(0.0, 1.5), (3.0, 4.0)]), 1.0),
+            "aselect='between(t,0.000,1.500)+between(t,3.000,4.000)',asetpts=N/SR/TB"
+        );
+        assert_eq!(filter_graph(None, 1.0), "anull");
+    }
+
+    #[test]
+    fn test_filter_args_with_many_ranges() {
+        // A three-hour talk with a cut every second
+        let kept: Vec<(f64, f64)> = (0..10_000).map(|i| (i as f64, i as f64 + 0.5)).collect();
+        let root = std::env::temp_dir().join(format!("ytx-preprocess-test-{}", std::process::id()));
+        let workspace = Workspace::create(&root).unwrap();
+
+        let args = filter_args(&workspace, Some(&kept), 1.25).unwrap();
+        assert_eq!(args[0], "-filter_script:a");
+        assert!(args.iter().map(String::len).sum::<usize>() < 1024);
+
+        let script = std::fs::read_to_string(&args[1]).unwrap();
+        assert_eq!(script, filter_graph(Some(&kept), 1.25));
+        assert_eq!(script.matches("between(").count(), 10_000);
+        assert!(script.ends_with(",atempo=1.25"));
+    }
+
+    #[test]
+    fn test_parse_speed() {
+        assert_eq!(parse_speed("1.5").unwrap(), 1.5);
+        assert!(parse_speed("0.5").is_err());
+        assert!(parse_speed("3").is_err());
+        assert!(parse_speed("fast").is_err());
+    }
+}
@@ -0,0 +1,310 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Quality, Segment};
+
+/// Whisper's own thresholds for a failed decode: gzip ratio of the text
+/// (loops compress well), mean token log-probability, and the probability
+/// that the audio held no speech at all
+const COMPRESSION_RATIO_THRESHOLD: f64 = 2.4;
+const LOGPROB_THRESHOLD: f64 = -1.0;
+const NO_SPEECH_THRESHOLD: f64 = 0.6;
+
+/// Lower no-speech bar for stock phrases, which are almost never real speech
+/// when the model suspects silence at all
+const STOCK_PHRASE_NO_SPEECH: f64 = 0.3;
+
+/// Phrases Whisper invents over silence and music, learned from subtitled
+/// video endings (compared after lowercasing and stripping punctuation)
+const STOCK_PHRASES: &[&str] = &[
+    "thanks for watching",
+    "thank you for watching",
+    "thank you so much for watching",
+    "please subscribe",
+    "like and subscribe",
+    "dont forget to like and subscribe",
+    "subtitles by the amaraorg community",
+    "transcription by castingwords",
+    "thank you",
+    "you",
+];
+
+/// Longest phrase checked for back-to-back repetition
+const MAX_LOOP_WORDS: usize = 8;
+
+/// A repeat must span at least this many words before it counts as a loop,
+/// so "no, no, no" survives but a phrase stuck on repeat doesn't
+const MIN_LOOP_SPAN_WORDS: usize = 8;
+
+/// Decoder statistics reported per segment in verbose_json
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SegmentStats {
+    pub avg_logprob: Option<f64>,
+    pub no_speech_prob: Option<f64>,
+    pub compression_ratio: Option<f64>,
+}
+
+impl SegmentStats {
+    pub fn from_json(seg: &serde_json::Value) -> Self {
+        Self {
+            avg_logprob: seg.get("avg_logprob").and_then(|v| v.as_f64()),
+            no_speech_prob: seg.get("no_speech_prob").and_then(|v| v.as_f64()),
+            compression_ratio: seg.get("compression_ratio").and_then(|v| v.as_f64()),
+        }
+    }
+}
+
+/// What to do with a segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Keep,
+    /// Keep, but mark as a likely hallucination
+    Flag,
+    Drop,
+}
+
+/// Judge a segment on its text and decoder statistics
+pub fn assess(text: &str, stats: &SegmentStats) -> Verdict {
+    let no_speech = stats.no_speech_prob.unwrap_or_default();
+    let low_confidence = stats.avg_logprob.is_some_and(|p| p < LOGPROB_THRESHOLD);
+
+    // Whisper itself treats this combination as silence
+    if no_speech > NO_SPEECH_THRESHOLD && low_confidence {
+        return Verdict::Drop;
+    }
+
+    if is_stock_phrase(text) {
+        return match stats.no_speech_prob {
+            Some(p) if p > STOCK_PHRASE_NO_SPEECH => Verdict::Drop,
+            Some(_) => Verdict::Keep,
+            // Without statistics there's no telling, so just mark it
+            None => Verdict::Flag,
+        };
+    }
+
+    if low_confidence || stats.compression_ratio.is_some_and(|r| r > COMPRESSION_RATIO_THRESHOLD) {
+        return Verdict::Flag;
+    }
+
+    Verdict::Keep
+}
+
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn is_stock_phrase(text: &str) -> bool {
+    let text = normalize(text);
+    STOCK_PHRASES.iter().any(|phrase| {
+        // Short phrases only count when they are the whole segment
+        text == *phrase || (phrase.contains(' ') && phrase.len() > 12 && text.contains(phrase))
+    })
+}
+
+/// Remove back-to-back repeats of a phrase within `text`, keeping one copy.
+/// Returns `None` if there was no loop.
+pub fn collapse_loops(text: &str) -> Option<String> {
+    let mut words: Vec<&str> = text.split_whitespace().collect();
+    let mut collapsed = false;
+
+    for n in 1..=MAX_LOOP_WORDS {
+        let min_repeats = MIN_LOOP_SPAN_WORDS.div_ceil(n).max(3);
+        let mut i = 0;
+        while i + n * min_repeats <= words.len() {
+            let phrase: Vec<String> = words[i..i + n].iter().map(|w| normalize(w)).collect();
+            let mut repeats = 1;
+            while i + n * (repeats + 1) <= words.len()
+                && words[i + n * repeats..i + n * (repeats + 1)]
+                    .iter()
+                    .zip(&phrase)
+                    .all(|(w, p)| normalize(w) == *p)
+            {
+                repeats += 1;
+            }
+            if repeats >= min_repeats {
+                words.drain(i + n..i + n * repeats);
+                collapsed = true;
+            }
+            i += 1;
+        }
+    }
+
+    collapsed.then(|| words.join(" "))
+}
+
+/// Merge runs of three or more consecutive segments with the same text into
+/// the first, which is stretched to cover the run and marked suspect.
+/// Returns how many runs were merged.
+pub fn collapse_repeated_segments(segments: &mut Vec<Segment>) -> usize {
+    let mut out = Vec::with_capacity(segments.len());
+    let mut merged = 0;
+    let mut rest = std::mem::take(segments).into_iter().peekable();
+
+    while let Some(mut first) = rest.next() {
+        let key = normalize(&first.text);
+        let mut run = Vec::new();
+        while let Some(next) = rest.next_if(|s| !key.is_empty() && normalize(&s.text) == key) {
+            run.push(next);
+        }
+
+        // Two in a row can be genuine ("Thank you." "Thank you."); three is a loop
+        if let Some(last) = run.last().filter(|_| run.len() >= 2) {
+            first.duration = (last.start + last.duration - first.start).max(first.duration);
+            first.suspect = true;
+            merged += 1;
+            out.push(first);
+        } else {
+            out.push(first);
+            out.extend(run);
+        }
+    }
+
+    *segments = out;
+    merged
+}
+
+/// Running counts behind a transcript's quality score, kept per upload and
+/// summed across chunks
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Tally {
+    pub segments: usize,
+    pub dropped: usize,
+    pub flagged: usize,
+    pub collapsed: usize,
+    logprob_sum: f64,
+    logprob_count: usize,
+}
+
+impl Tally {
+    pub fn record(&mut self, verdict: Verdict, stats: &SegmentStats) {
+        self.segments += 1;
+        match verdict {
+            Verdict::Keep => {}
+            Verdict::Flag => self.flagged += 1,
+            Verdict::Drop => self.dropped += 1,
+        }
+        if verdict != Verdict::Drop
+            && let Some(p) = stats.avg_logprob
+        {
+            self.logprob_sum += p;
+            self.logprob_count += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &Tally) {
+        self.segments += other.segments;
+        self.dropped += other.dropped;
+        self.flagged += other.flagged;
+        self.collapsed += other.collapsed;
+        self.logprob_sum += other.logprob_sum;
+        self.logprob_count += other.logprob_count;
+    }
+
+    /// The share of segments with no problems, scaled by the model's mean
+    /// token probability where it was reported
+    pub fn quality(&self) -> Quality {
+        let problems = self.dropped + self.flagged + self.collapsed;
+        let clean = self.segments.saturating_sub(problems) as f64 / self.segments.max(1) as f64;
+        let confidence = if self.logprob_count > 0 {
+            (self.logprob_sum / self.logprob_count as f64).exp()
+        } else {
+            1.0
+        };
+        Quality {
+            score: (clean * confidence * 100.0).round() / 100.0,
+            dropped: self.dropped,
+            flagged: self.flagged,
+            collapsed: self.collapsed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(avg_logprob: f64, no_speech_prob: f64, compression_ratio: f64) -> SegmentStats {
+        SegmentStats {
+            avg_logprob: Some(avg_logprob),
+            no_speech_prob: Some(no_speech_prob),
+            compression_ratio: Some(compression_ratio),
+        }
+    }
+
+    fn segment(text: &str, start: f64) -> Segment {
+        Segment {
+            text: text.to_string(),
+            start,
+            duration: 1.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_assess() {
+        assert_eq!(assess("Welcome to the show.", &stats(-0.2, 0.01, 1.3)), Verdict::Keep);
+        assert_eq!(assess("Hmm.", &stats(-1.4, 0.8, 1.0)), Verdict::Drop);
+        assert_eq!(assess("Thanks for watching!", &stats(-0.4, 0.45, 1.0)), Verdict::Drop);
+        assert_eq!(assess("Thanks for watching!", &stats(-0.4, 0.05, 1.0)), Verdict::Keep);
+        assert_eq!(assess("Thanks for watching!", &SegmentStats::default()), Verdict::Flag);
+        assert_eq!(assess("the the the the", &stats(-0.3, 0.01, 3.1)), Verdict::Flag);
+        assert_eq!(assess("mumble", &stats(-1.2, 0.1, 1.0)), Verdict::Flag);
+    }
+
+    #[test]
+    fn test_stock_phrase_must_be_whole_segment_when_short() {
+        assert!(is_stock_phrase("You."));
+        assert!(!is_stock_phrase("You know what I mean."));
+        assert!(is_stock_phrase("Subtitles by the Amara.org community"));
+    }
+
+    #[test]
+    fn test_collapse_loops() {
+        assert_eq!(
+            collapse_loops("and I said we need to go we need to go we need to go we need to go now").as_deref(),
+            Some("and I said we need to go now")
+        );
+        assert_eq!(collapse_loops("no, no, no, that's wrong"), None);
+        assert_eq!(collapse_loops("A normal sentence with no loops."), None);
+    }
+
+    #[test]
+    fn test_collapse_repeated_segments() {
+        let mut segments = vec![
+            segment("Intro", 0.0),
+            segment("Thank you.", 1.0),
+            segment("Thank you.", 2.0),
+            segment("Outro", 3.0),
+            segment("Go.", 4.0),
+            segment("Go.", 5.0),
+            segment("Go.", 6.0),
+            segment("Go.", 7.0),
+        ];
+        assert_eq!(collapse_repeated_segments(&mut segments), 1);
+        let texts: Vec<&str> = segments.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, vec!["Intro", "Thank you.", "Thank you.", "Outro", "Go."]);
+        assert_eq!(segments[4].start, 4.0);
+        assert_eq!(segments[4].duration, 4.0);
+        assert!(segments[4].suspect);
+        assert!(!segments[1].suspect);
+    }
+
+    #[test]
+    fn test_tally_quality() {
+        let mut tally = Tally::default();
+        tally.record(Verdict::Keep, &stats(-0.1, 0.0, 1.0));
+        tally.record(Verdict::Keep, &stats(-0.1, 0.0, 1.0));
+        tally.record(Verdict::Flag, &stats(-0.1, 0.0, 3.0));
+        tally.record(Verdict::Drop, &stats(-2.0, 0.9, 1.0));
+        let quality = tally.quality();
+        assert_eq!(quality.dropped, 1);
+        assert_eq!(quality.flagged, 1);
+        // Half the segments are clean, at a mean token probability of e^-0.1
+        assert_eq!(quality.score, 0.45);
+    }
+}
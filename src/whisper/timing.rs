@@ -0,0 +1,236 @@
+use super::Silence;
+use crate::Segment;
+
+/// Longest cue produced from one sentence before it is split at words
+const MAX_CUE_CHARS: usize = 160;
+
+/// How far a cue boundary may move to land in a pause
+const SNAP_WINDOW_SECS: f64 = 2.0;
+
+const SENTENCE_ENDS: [char; 7] = ['.', '!', '?', '…', '。', '！', '？'];
+
+/// Whether segments carry no real timing, as from a plain-text response
+pub fn is_untimed(segments: &[Segment]) -> bool {
+    !segments.is_empty() && segments.iter().all(|s| s.start == 0.0 && s.duration == 0.0)
+}
+
+/// Estimated segments for text that came back without timestamps.
+///
+/// The text is split into sentence cues, which are spread over the speech in
+/// `duration` seconds of audio in proportion to their length, skipping the
+/// `silences`; boundaries near a pause are then moved into it.
+pub fn synthesize_segments(text: &str, duration: f64, silences: &[Silence]) -> Vec<Segment> {
+    let cues = split_cues(text);
+    if cues.is_empty() {
+        return Vec::new();
+    }
+
+    let silences: Vec<Silence> = silences
+        .iter()
+        .map(|s| Silence {
+            start: s.start.max(0.0),
+            end: s.end.min(duration),
+        })
+        .filter(|s| s.end > s.start)
+        .collect();
+    let silent: f64 = silences.iter().map(|s| s.end - s.start).sum();
+    let speech = (duration - silent).max(0.0);
+
+    let weights: Vec<usize> = cues
+        .iter()
+        .map(|c| c.chars().filter(|ch| !ch.is_whitespace()).count())
+        .collect();
+    let total = weights.iter().sum::<usize>().max(1) as f64;
+
+    let mut spans = Vec::with_capacity(cues.len());
+    let mut done = 0usize;
+    for weight in &weights {
+        let from = done as f64 / total * speech;
+        done += weight;
+        let to = done as f64 / total * speech;
+        spans.push((
+            speech_to_real(from, &silences, true),
+            speech_to_real(to, &silences, false),
+        ));
+    }
+
+    snap_to_pauses(&mut spans, &silences);
+
+    cues.into_iter()
+        .zip(spans)
+        .map(|(text, (start, end))| Segment {
+            text,
+            start,
+            duration: (end - start).max(0.0),
+            estimated: true,
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Position in the audio reached after `speech` seconds of non-silence. A
+/// start lands after a silence beginning exactly there, an end before it.
+fn speech_to_real(speech: f64, silences: &[Silence], is_start: bool) -> f64 {
+    let mut t = speech;
+    for silence in silences {
+        let passed = if is_start {
+            silence.start <= t
+        } else {
+            silence.start < t
+        };
+        if !passed {
+            break;
+        }
+        t += silence.end - silence.start;
+    }
+    t
+}
+
+/// Move each boundary between cues into a pause within reach, so one cue
+/// ends where the pause starts and the next begins where it ends
+fn snap_to_pauses(spans: &mut [(f64, f64)], silences: &[Silence]) {
+    for i in 1..spans.len() {
+        let boundary = spans[i].0;
+        let (prev_start, next_end) = (spans[i - 1].0, spans[i].1);
+        let nearest = silences
+            .iter()
+            .filter(|s| s.start > prev_start && s.end < next_end)
+            .filter(|s| (s.midpoint() - boundary).abs() <= SNAP_WINDOW_SECS)
+            .min_by(|a, b| {
+                (a.midpoint() - boundary)
+                    .abs()
+                    .total_cmp(&(b.midpoint() - boundary).abs())
+            });
+        if let Some(silence) = nearest {
+            spans[i - 1].1 = silence.start;
+            spans[i].0 = silence.end;
+        }
+    }
+}
+
+/// Split text into sentences, breaking overlong ones at word boundaries
+fn split_cues(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        current.push(c);
+        // CJK full stops end a sentence without a following space
+        let ends = SENTENCE_ENDS.contains(&c) && (!c.is_ascii() || chars.peek().is_none_or(|n| n.is_whitespace()));
+        if ends && !current.trim().is_empty() {
+            sentences.push(current.trim().to_string());
+            current.clear();
+        }
+    }
+    if !current.trim().is_empty() {
+        sentences.push(current.trim().to_string());
+    }
+
+    sentences.into_iter().flat_map(|s| split_long(&s)).collect()
+}
+
+/// Break a sentence into roughly equal pieces of at most `MAX_CUE_CHARS`
+fn split_long(sentence: &str) -> Vec<String> {
+    let len = sentence.chars().count();
+    if len <= MAX_CUE_CHARS {
+        return vec![sentence.to_string()];
+    }
+
+    let pieces = len.div_ceil(MAX_CUE_CHARS);
+    let target = len / pieces;
+    let mut out = Vec::with_capacity(pieces);
+    let mut current = String::new();
+
+    for word in sentence.split_whitespace() {
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+        if current.chars().count() >= target && out.len() + 1 < pieces {
+            out.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        out.push(current);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn silence(start: f64, end: f64) -> Silence {
+        Silence { start, end }
+    }
+
+    #[test]
+    fn test_split_cues() {
+        assert_eq!(
+            split_cues("Hello there. How are you? Version 1.5 is out!"),
+            vec!["Hello there.", "How are you?", "Version 1.5 is out!"]
+        );
+        assert_eq!(
+            split_cues("こんにちは。元気ですか？"),
+            vec!["こんにちは。", "元気ですか？"]
+        );
+        assert_eq!(split_cues("no punctuation at all"), vec!["no punctuation at all"]);
+    }
+
+    #[test]
+    fn test_split_long_sentence() {
+        let sentence = vec!["word"; 80].join(" ");
+        let cues = split_cues(&sentence);
+        assert_eq!(cues.len(), 3);
+        assert!(cues.iter().all(|c| c.chars().count() <= MAX_CUE_CHARS));
+        assert_eq!(cues.join(" "), sentence);
+    }
+
+    #[test]
+    fn test_synthesize_weights_by_length() {
+        // 10 characters then 30: a quarter and three quarters of 40s
+        let text = format!("{}. {}.", "a".repeat(9), "b".repeat(29));
+        let segments = synthesize_segments(&text, 40.0, &[]);
+        assert_eq!(segments.len(), 2);
+        assert!(segments.iter().all(|s| s.estimated));
+        assert_eq!(segments[0].start, 0.0);
+        assert!((segments[0].duration - 10.0).abs() < 1e-9);
+        assert!((segments[1].start - 10.0).abs() < 1e-9);
+        assert!((segments[1].duration - 30.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_synthesize_skips_silence() {
+        // Leading 10s of silence; speech fills the remaining 20s
+        let segments = synthesize_segments("One two. Three four.", 30.0, &[silence(0.0, 10.0)]);
+        assert_eq!(segments[0].start, 10.0);
+        let last = segments.last().unwrap();
+        assert!((last.start + last.duration - 30.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_synthesize_snaps_boundary_into_pause() {
+        // Equal halves would split at 10s; a pause at 11-12s is within reach
+        let segments = synthesize_segments("Aaaa bbbb. Cccc dddd.", 21.0, &[silence(11.0, 12.0)]);
+        assert!((segments[0].duration - 11.0).abs() < 1e-9);
+        assert_eq!(segments[1].start, 12.0);
+    }
+
+    #[test]
+    fn test_is_untimed() {
+        let plain = vec![Segment {
+            text: "text".to_string(),
+            ..Default::default()
+        }];
+        assert!(is_untimed(&plain));
+        assert!(!is_untimed(&[]));
+        let timed = vec![Segment {
+            text: "text".to_string(),
+            start: 0.0,
+            duration: 1.5,
+            ..Default::default()
+        }];
+        assert!(!is_untimed(&timed));
+    }
+}
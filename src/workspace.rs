@@ -0,0 +1,115 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use eyre::{Result, bail};
+use log::debug;
+
+/// Workspaces still on disk, so a signal handler can remove them before exit
+static ACTIVE: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A private scratch directory for one run's intermediate files (downloaded
+/// audio, chunks). It is removed when dropped, whether the run succeeded or
+/// failed, and by [`cleanup_all`] on interrupt.
+#[derive(Debug)]
+pub struct Workspace {
+    path: PathBuf,
+}
+
+impl Workspace {
+    /// Create a new, uniquely named directory under `root`
+    pub fn create(root: &Path) -> Result<Self> {
+        std::fs::create_dir_all(root)?;
+
+        for _ in 0..100 {
+            let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.subsec_nanos())
+                .unwrap_or_default();
+            let path = root.join(format!("ytx-{}-{id}-{nanos:08x}", std::process::id()));
+
+            // create_dir fails if the path exists, so the name is ours alone
+            match std::fs::create_dir(&path) {
+                Ok(()) => {
+                    debug!("Created workspace: {}", path.display());
+                    ACTIVE.lock().unwrap_or_else(|e| e.into_inner()).push(path.clone());
+                    return Ok(Self { path });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => bail!("failed to create workspace in {}: {e}", root.display()),
+            }
+        }
+
+        bail!("failed to create a unique workspace in {}", root.display());
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path for a file inside the workspace
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        remove(&self.path);
+        ACTIVE
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|p| p != &self.path);
+    }
+}
+
+/// Default root for workspaces: the system temp directory (honours `TMPDIR`)
+pub fn default_root() -> PathBuf {
+    std::env::temp_dir()
+}
+
+/// Remove every live workspace; for use when the process is about to exit
+/// without unwinding (e.g. on SIGINT/SIGTERM)
+pub fn cleanup_all() {
+    let paths = std::mem::take(&mut *ACTIVE.lock().unwrap_or_else(|e| e.into_inner()));
+    for path in &paths {
+        remove(path);
+    }
+}
+
+fn remove(path: &Path) {
+    match std::fs::remove_dir_all(path) {
+        Ok(()) => debug!("Removed workspace: {}", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => debug!("Failed to remove workspace {}: {e}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_root(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ytx-workspace-test-{name}-{}", std::process::id()))
+    }
+
+    #[test]
+    fn test_workspaces_are_unique_and_removed_on_drop() {
+        let root = test_root("drop");
+        let a = Workspace::create(&root).unwrap();
+        let b = Workspace::create(&root).unwrap();
+        assert_ne!(a.path(), b.path());
+
+        std::fs::write(a.file("audio.mp3"), b"data").unwrap();
+        let a_path = a.path().to_path_buf();
+        drop(a);
+        assert!(!a_path.exists());
+        assert!(b.path().exists());
+
+        drop(b);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -1,9 +1,10 @@
 use eyre::{Result, bail};
 use log::debug;
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use crate::{Segment, Transcript, TranscriptSource};
+use crate::{AUTO_LANG, Segment, Transcript, TranscriptSource};
 
 const USER_AGENT: &str =
     "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36";
@@ -18,6 +19,8 @@ struct InnerTubePlayerResponse {
 #[derive(Debug, Deserialize)]
 struct VideoDetails {
     title: Option<String>,
+    #[serde(rename = "lengthSeconds")]
+    length_seconds: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -32,16 +35,114 @@ struct CaptionTracklistRenderer {
     caption_tracks: Option<Vec<CaptionTrack>>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 struct CaptionTrack {
     #[serde(rename = "baseUrl")]
     base_url: String,
     #[serde(rename = "languageCode")]
     language_code: String,
+    /// "asr" for auto-generated tracks, absent for ones written by a person
+    kind: Option<String>,
+}
+
+impl CaptionTrack {
+    fn is_manual(&self) -> bool {
+        self.kind.as_deref() != Some("asr")
+    }
+}
+
+/// Who to look a video up as. YouTube sometimes gives caption tracks to its
+/// apps that it refuses the web player.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InnerTubeClient {
+    #[default]
+    Web,
+    Android,
+    Ios,
+    /// The embedded TV player
+    Tv,
+}
+
+impl InnerTubeClient {
+    /// The request's `client` context, apart from the language fields
+    fn context(self) -> Value {
+        match self {
+            Self::Web => serde_json::json!({"clientName": "WEB", "clientVersion": "2.20241126.01.00"}),
+            Self::Android => serde_json::json!({
+                "clientName": "ANDROID",
+                "clientVersion": "19.44.38",
+                "androidSdkVersion": 30,
+                "osName": "Android",
+                "osVersion": "11"
+            }),
+            Self::Ios => serde_json::json!({
+                "clientName": "IOS",
+                "clientVersion": "19.45.4",
+                "deviceMake": "Apple",
+                "deviceModel": "iPhone16,2",
+                "osName": "iPhone",
+                "osVersion": "18.1.0.22B83"
+            }),
+            Self::Tv => serde_json::json!({"clientName": "TVHTML5_SIMPLY_EMBEDDED_PLAYER", "clientVersion": "2.0"}),
+        }
+    }
+
+    fn user_agent(self) -> &'static str {
+        match self {
+            Self::Android => "com.google.android.youtube/19.44.38 (Linux; U; Android 11) gzip",
+            Self::Ios => "com.google.ios.youtube/19.45.4 (iPhone16,2; U; CPU iOS 18_1_0 like Mac OS X;)",
+            Self::Web | Self::Tv => USER_AGENT,
+        }
+    }
+}
+
+impl std::fmt::Display for InnerTubeClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Web => "web",
+            Self::Android => "android",
+            Self::Ios => "ios",
+            Self::Tv => "tv",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A video's details and caption tracks, as the InnerTube player reports them
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub title: String,
+    /// Length in seconds
+    pub duration: Option<f64>,
+    tracks: Vec<CaptionTrack>,
+}
+
+impl Player {
+    /// Whether any caption track was written by a person rather than generated
+    pub fn has_manual_captions(&self) -> bool {
+        self.tracks.iter().any(CaptionTrack::is_manual)
+    }
 }
 
 /// Fetch transcript from YouTube's built-in captions via the InnerTube API
 pub async fn fetch_captions(client: &reqwest::Client, video_id: &str, lang: &str) -> Result<Transcript> {
+    let player = fetch_player(client, video_id, lang).await?;
+    fetch_track(client, video_id, &player, lang, false).await
+}
+
+/// Look up a video's details and caption tracks via the InnerTube API
+pub async fn fetch_player(client: &reqwest::Client, video_id: &str, lang: &str) -> Result<Player> {
+    fetch_player_as(client, video_id, lang, InnerTubeClient::Web).await
+}
+
+/// Look up a video as the given InnerTube client
+pub async fn fetch_player_as(
+    client: &reqwest::Client,
+    video_id: &str,
+    lang: &str,
+    as_client: InnerTubeClient,
+) -> Result<Player> {
     // Step 1: Fetch the watch page to get the InnerTube API key
     let watch_url = format!("https://www.youtube.com/watch?v={video_id}");
     debug!("Fetching watch page: {watch_url}");
@@ -61,21 +162,10 @@ pub async fn fetch_captions(client: &reqwest::Client, video_id: &str, lang: &str
     // Step 2: Call InnerTube player endpoint
     let player_url = format!("https://www.youtube.com/youtubei/v1/player?key={api_key}&prettyPrint=false");
 
-    let body = serde_json::json!({
-        "context": {
-            "client": {
-                "hl": lang,
-                "gl": "US",
-                "clientName": "WEB",
-                "clientVersion": "2.20241126.01.00"
-            }
-        },
-        "videoId": video_id
-    });
-
+    let body = player_body(video_id, lang, as_client);
     let resp: InnerTubePlayerResponse = client
         .post(&player_url)
-        .header("User-Agent", USER_AGENT)
+        .header("User-Agent", as_client.user_agent())
         .header("Content-Type", "application/json")
         .json(&body)
         .send()
@@ -84,10 +174,14 @@ pub async fn fetch_captions(client: &reqwest::Client, video_id: &str, lang: &str
         .json()
         .await?;
 
-    let title = resp
+    let (title, duration) = resp
         .video_details
-        .as_ref()
-        .and_then(|vd| vd.title.clone())
+        .map(|vd| {
+            (
+                vd.title.unwrap_or_default(),
+                vd.length_seconds.and_then(|s| s.parse().ok()),
+            )
+        })
         .unwrap_or_default();
 
     let tracks = resp
@@ -96,23 +190,53 @@ pub async fn fetch_captions(client: &reqwest::Client, video_id: &str, lang: &str
         .and_then(|r| r.caption_tracks)
         .unwrap_or_default();
 
-    if tracks.is_empty() {
-        bail!("no captions available for video {video_id}");
+    Ok(Player {
+        title,
+        duration,
+        tracks,
+    })
+}
+
+fn player_body(video_id: &str, lang: &str, as_client: InnerTubeClient) -> Value {
+    let mut context = as_client.context();
+    context["hl"] = (if lang == AUTO_LANG { "en" } else { lang }).into();
+    context["gl"] = "US".into();
+    let mut body = serde_json::json!({
+        "context": {
+            "client": context
+        },
+        "videoId": video_id
+    });
+    if as_client == InnerTubeClient::Tv {
+        // The embedded player only plays videos it's told are embedded
+        body["context"]["thirdParty"] = serde_json::json!({"embedUrl": "https://www.youtube.com/"});
     }
+    body
+}
 
-    // Find the requested language track, or fall back to the first available
-    let track = tracks
-        .iter()
-        .find(|t| t.language_code == lang)
-        .or_else(|| tracks.first())
-        .unwrap(); // safe: tracks is non-empty
+/// Download the caption track for `lang` from a looked-up video, ignoring
+/// auto-generated tracks when `manual_only`
+pub async fn fetch_track(
+    client: &reqwest::Client,
+    video_id: &str,
+    player: &Player,
+    lang: &str,
+    manual_only: bool,
+) -> Result<Transcript> {
+    let track = match select_track(&player.tracks, lang, manual_only) {
+        Some(track) => track,
+        None if manual_only && !player.tracks.is_empty() => {
+            bail!("only auto-generated captions available for video {video_id}")
+        }
+        None => bail!("no captions available for video {video_id}"),
+    };
 
     let actual_lang = track.language_code.clone();
     debug!("Using caption track: lang={actual_lang}");
 
     // Step 3: Fetch the caption XML
     let caption_xml = client
-        .get(&track.base_url)
+        .get(track_url(&track.base_url))
         .header("User-Agent", USER_AGENT)
         .send()
         .await?
@@ -124,13 +248,32 @@ pub async fn fetch_captions(client: &reqwest::Client, video_id: &str, lang: &str
 
     Ok(Transcript {
         video_id: video_id.to_string(),
-        title,
+        title: player.title.clone(),
         language: actual_lang,
+        source_language: None,
         source: TranscriptSource::Caption,
         segments,
+        speakers: Default::default(),
+        quality: None,
     })
 }
 
+/// The requested language track, or else the first available
+fn select_track<'a>(tracks: &'a [CaptionTrack], lang: &str, manual_only: bool) -> Option<&'a CaptionTrack> {
+    let usable = || tracks.iter().filter(move |t| !manual_only || t.is_manual());
+    usable().find(|t| t.language_code == lang).or_else(|| usable().next())
+}
+
+/// A track's URL without any `fmt` parameter, which the apps' tracks carry
+/// and which would ask for a format other than the timed-text XML we parse
+fn track_url(base_url: &str) -> String {
+    Regex::new(r"([?&])fmt=[^&]*&?")
+        .expect("valid regex")
+        .replace(base_url, "$1")
+        .trim_end_matches(['&', '?'])
+        .to_string()
+}
+
 fn extract_api_key(html: &str) -> Result<String> {
     let re = Regex::new(r#""INNERTUBE_API_KEY"\s*:\s*"([^"]+)""#)?;
     if let Some(caps) = re.captures(html) {
@@ -186,6 +329,7 @@ fn parse_caption_xml(xml: &str) -> Result<Vec<Segment>> {
                             text,
                             start,
                             duration: dur,
+                            ..Default::default()
                         });
                     }
                 }
@@ -223,6 +367,54 @@ mod tests {
         assert!(extract_api_key(html).is_err());
     }
 
+    fn track(lang: &str, kind: Option<&str>) -> CaptionTrack {
+        CaptionTrack {
+            base_url: format!("https://example.com/{lang}"),
+            language_code: lang.to_string(),
+            kind: kind.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_select_track() {
+        let tracks = [track("de", None), track("en", Some("asr"))];
+        assert_eq!(select_track(&tracks, "en", false).unwrap().language_code, "en");
+        assert_eq!(select_track(&tracks, "fr", false).unwrap().language_code, "de");
+        // The English track is auto-generated, so the manual German one wins
+        assert_eq!(select_track(&tracks, "en", true).unwrap().language_code, "de");
+        assert!(select_track(&tracks[1..], "en", true).is_none());
+    }
+
+    #[test]
+    fn test_player_body() {
+        let web = player_body("abc123", "de", InnerTubeClient::Web);
+        assert_eq!(web["context"]["client"]["clientName"], "WEB");
+        assert_eq!(web["context"]["client"]["hl"], "de");
+        assert!(web["context"].get("thirdParty").is_none());
+
+        let android = player_body("abc123", AUTO_LANG, InnerTubeClient::Android);
+        assert_eq!(android["context"]["client"]["clientName"], "ANDROID");
+        assert_eq!(android["context"]["client"]["hl"], "en");
+
+        let tv = player_body("abc123", "en", InnerTubeClient::Tv);
+        assert_eq!(tv["context"]["thirdParty"]["embedUrl"], "https://www.youtube.com/");
+        assert_eq!(tv["videoId"], "abc123");
+    }
+
+    #[test]
+    fn test_track_url_drops_format() {
+        assert_eq!(
+            track_url("https://www.youtube.com/api/timedtext?v=abc&fmt=srv3&lang=en"),
+            "https://www.youtube.com/api/timedtext?v=abc&lang=en"
+        );
+        assert_eq!(
+            track_url("https://www.youtube.com/api/timedtext?v=abc&lang=en&fmt=srv3"),
+            "https://www.youtube.com/api/timedtext?v=abc&lang=en"
+        );
+        let plain = "https://www.youtube.com/api/timedtext?v=abc&lang=en";
+        assert_eq!(track_url(plain), plain);
+    }
+
     #[test]
     fn test_parse_caption_xml_basic() {
         let xml = r#"<?xml version="1.0" encoding="utf-8" ?>